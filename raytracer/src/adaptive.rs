@@ -0,0 +1,75 @@
+use crate::framebuffer::FrameBuffer;
+
+// no pixel takes more than this many times the average number of samples
+// in one call
+const MAX_FACTOR: u32 = 16;
+
+// the first pass, enough samples to estimate the variance of every pixel
+fn first_pass(samples_per_pixel: u32) -> u32 {
+    (samples_per_pixel / 8).max(4).min(samples_per_pixel)
+}
+
+// Spends `samples_per_pixel` samples per pixel on average over the buffer.
+// After an even first pass over the pixels without samples yet, the rest of
+// the budget goes out in rounds to the pixels whose relative error is still
+// above `threshold`, in proportion to that error; pixels below it take no
+// more samples. `sample(buffer, i, j, n)` adds `n` samples to a pixel. The
+// buffer may already hold samples from earlier passes, their variance counts
+// as well.
+//
+// The budget is per buffer, i.e. per tile, so the result does not depend on
+// the order in which the tiles are rendered.
+pub fn render<F>(buffer: &mut FrameBuffer, samples_per_pixel: u32, threshold: f64, mut sample: F)
+where
+    F: FnMut(&mut FrameBuffer, u32, u32, u32),
+{
+    let (width, height) = (buffer.width(), buffer.height());
+    let first = first_pass(samples_per_pixel);
+    let max = samples_per_pixel.saturating_mul(MAX_FACTOR);
+    let start: Vec<u32> = (0..height)
+        .flat_map(|j| (0..width).map(move |i| (i, j)))
+        .map(|(i, j)| buffer.samples(i, j))
+        .collect();
+    let taken = |buffer: &FrameBuffer, i: u32, j: u32| {
+        buffer.samples(i, j) - start[(j * width + i) as usize]
+    };
+    let mut remaining = samples_per_pixel as u64 * (width * height) as u64;
+    for j in 0..height {
+        for i in 0..width {
+            if buffer.samples(i, j) == 0 {
+                sample(buffer, i, j, first);
+                remaining -= first as u64;
+            }
+        }
+    }
+
+    while remaining > 0 {
+        let mut noisy = vec![];
+        for j in 0..height {
+            for i in 0..width {
+                let error = buffer.relative_error(i, j);
+                if error > threshold && taken(buffer, i, j) < max {
+                    noisy.push((i, j, error));
+                }
+            }
+        }
+        let total: f64 = noisy.iter().map(|&(_, _, error)| error).sum();
+        // a round hands out about as many samples as the first pass, so the
+        // estimates are refreshed before the next one
+        let round = remaining.min(noisy.len() as u64 * first as u64) as f64;
+        let mut spent = 0;
+        for (i, j, error) in noisy {
+            let share = (round * error / total).ceil() as u64;
+            let count = share
+                .min((max - taken(buffer, i, j)) as u64)
+                .min(remaining - spent) as u32;
+            sample(buffer, i, j, count);
+            spent += count as u64;
+        }
+        // every pixel has converged or hit the cap
+        if spent == 0 {
+            break;
+        }
+        remaining -= spent;
+    }
+}
@@ -0,0 +1,158 @@
+use crate::camera::CameraSettings;
+use crate::vec3::Point3;
+use std::array;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    // a cubic through every key, smooth at the keys
+    Spline,
+}
+
+pub const INTERPOLATIONS: [&str; 2] = ["linear", "spline"];
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Interpolation::Linear),
+            "spline" => Some(Interpolation::Spline),
+            _ => None,
+        }
+    }
+}
+
+// The animated part of the camera at one frame
+#[derive(Copy, Clone, Debug)]
+pub struct CameraKey {
+    pub frame: f64,
+    pub look_from: Point3,
+    pub look_at: Point3,
+    pub vfov: f64,
+    pub focus_dist: f64,
+    pub defocus_angle: f64,
+}
+
+impl CameraKey {
+    // a pose for `frame` that keeps everything of `settings`
+    pub fn from_settings(frame: f64, settings: &CameraSettings) -> Self {
+        Self {
+            frame,
+            look_from: settings.look_from,
+            look_at: settings.look_at,
+            vfov: settings.vfov,
+            focus_dist: settings.focus_dist,
+            defocus_angle: settings.defocus_angle,
+        }
+    }
+    // every value as one vector, so they are all interpolated alike
+    fn values(&self) -> [f64; 9] {
+        let (f, a) = (self.look_from, self.look_at);
+        [
+            f.x,
+            f.y,
+            f.z,
+            a.x,
+            a.y,
+            a.z,
+            self.vfov,
+            self.focus_dist,
+            self.defocus_angle,
+        ]
+    }
+}
+
+// Values keyed at points in time, frames of a sequence or moments of the
+// shutter. Before the first and after the last key they hold still.
+#[derive(Clone, Debug)]
+pub struct Track<const N: usize> {
+    keys: Vec<(f64, [f64; N])>,
+    pub interpolation: Interpolation,
+}
+
+impl<const N: usize> Track<N> {
+    pub fn new(
+        mut keys: Vec<(f64, [f64; N])>,
+        interpolation: Interpolation,
+    ) -> Result<Self, String> {
+        if keys.is_empty() {
+            return Err(String::from("An animation needs at least one key"));
+        }
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if let Some(pair) = keys.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("Two keys at {}", pair[0].0));
+        }
+        Ok(Self {
+            keys,
+            interpolation,
+        })
+    }
+    pub fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.keys.iter().map(|key| key.0)
+    }
+    pub fn at(&self, time: f64) -> [f64; N] {
+        let keys = &self.keys;
+        let last = keys.len() - 1;
+        if time <= keys[0].0 {
+            return keys[0].1;
+        }
+        if time >= keys[last].0 {
+            return keys[last].1;
+        }
+        let k = keys.iter().rposition(|key| key.0 <= time).unwrap();
+        let (p0, p1) = (keys[k].1, keys[k + 1].1);
+        let h = keys[k + 1].0 - keys[k].0;
+        let t = (time - keys[k].0) / h;
+        match self.interpolation {
+            Interpolation::Linear => array::from_fn(|c| p0[c] + (p1[c] - p0[c]) * t),
+            // cubic Hermite with Catmull-Rom tangents, taken per unit of time
+            // so unevenly spaced keys still move smoothly
+            Interpolation::Spline => {
+                let (m0, m1) = (self.tangent(k), self.tangent(k + 1));
+                let (t2, t3) = (t * t, t * t * t);
+                let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+                let h10 = t3 - 2.0 * t2 + t;
+                let h01 = -2.0 * t3 + 3.0 * t2;
+                let h11 = t3 - t2;
+                array::from_fn(|c| h00 * p0[c] + h10 * h * m0[c] + h01 * p1[c] + h11 * h * m1[c])
+            }
+        }
+    }
+    // change per unit of time at key `k`, one-sided at the ends
+    fn tangent(&self, k: usize) -> [f64; N] {
+        let keys = &self.keys;
+        let before = k.saturating_sub(1);
+        let after = (k + 1).min(keys.len() - 1);
+        let (a, b) = (keys[before].1, keys[after].1);
+        let time = keys[after].0 - keys[before].0;
+        if time > 0.0 {
+            array::from_fn(|c| (b[c] - a[c]) / time)
+        } else {
+            [0.0; N]
+        }
+    }
+}
+
+// Keyframes of the camera
+#[derive(Clone, Debug)]
+pub struct CameraTrack {
+    track: Track<9>,
+}
+
+impl CameraTrack {
+    pub fn new(keys: Vec<CameraKey>, interpolation: Interpolation) -> Result<Self, String> {
+        let keys = keys.iter().map(|key| (key.frame, key.values())).collect();
+        Ok(Self {
+            track: Track::new(keys, interpolation)
+                .map_err(|e| format!("Camera animation: {}", e))?,
+        })
+    }
+    // sets the animated settings to their values at `frame`
+    pub fn apply(&self, frame: f64, settings: &mut CameraSettings) {
+        let v = self.track.at(frame);
+        settings.look_from = Point3::new(v[0], v[1], v[2]);
+        settings.look_at = Point3::new(v[3], v[4], v[5]);
+        settings.vfov = v[6];
+        settings.focus_dist = v[7];
+        settings.defocus_angle = v[8];
+    }
+}
@@ -0,0 +1,140 @@
+use crate::framebuffer::{Features, FrameBuffer};
+use crate::rng;
+use image::{Rgb, RgbImage};
+
+// Arbitrary output variables: what the camera saw at the first hit of every
+// pixel, next to the beauty image
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aov {
+    Depth,
+    Normal,
+    Albedo,
+    Uv,
+    Position,
+    ObjectId,
+}
+
+pub const AOVS: [&str; 6] = ["depth", "normal", "albedo", "uv", "position", "id"];
+
+impl Aov {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "depth" => Some(Aov::Depth),
+            "normal" => Some(Aov::Normal),
+            "albedo" => Some(Aov::Albedo),
+            "uv" => Some(Aov::Uv),
+            "position" => Some(Aov::Position),
+            "id" => Some(Aov::ObjectId),
+            _ => None,
+        }
+    }
+    pub fn all() -> Vec<Self> {
+        AOVS.iter()
+            .map(|name| Self::from_name(name).unwrap())
+            .collect()
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Normal => "normal",
+            Aov::Albedo => "albedo",
+            Aov::Uv => "uv",
+            Aov::Position => "position",
+            Aov::ObjectId => "id",
+        }
+    }
+    // the channels of its layer in a multi-layer file
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Albedo => &["R", "G", "B"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId => &["id"],
+        }
+    }
+    // one value per channel, pixels that saw nothing are 0
+    pub fn values(&self, features: &Features) -> Vec<f64> {
+        let Features {
+            albedo,
+            normal,
+            depth,
+            uv,
+            position,
+            object_id,
+        } = *features;
+        match self {
+            Aov::Depth if depth.is_finite() => vec![depth],
+            Aov::Depth => vec![0.0],
+            Aov::Normal => vec![normal.x, normal.y, normal.z],
+            Aov::Albedo => vec![albedo.x, albedo.y, albedo.z],
+            Aov::Uv => uv.to_vec(),
+            Aov::Position => vec![position.x, position.y, position.z],
+            Aov::ObjectId => vec![object_id as f64],
+        }
+    }
+    // raw values for the float formats, single channels are repeated in all three
+    pub fn to_linear(&self, framebuffer: &FrameBuffer) -> Vec<Rgb<f32>> {
+        let mut pixels = vec![];
+        for j in 0..framebuffer.height() {
+            for i in 0..framebuffer.width() {
+                let values = self.values(&framebuffer.features(i, j));
+                let channel = |c: usize| match values.len() {
+                    1 => values[0] as f32,
+                    _ => values.get(c).copied().unwrap_or(0.0) as f32,
+                };
+                pixels.push(Rgb([channel(0), channel(1), channel(2)]));
+            }
+        }
+        pixels
+    }
+    // Something to look at for the 8-bit formats: depth from white (nearest)
+    // to black (farthest), normals and uv shifted into [0, 1], positions scaled to the
+    // box around what is visible and a random color for every id.
+    pub fn to_rgb_image(&self, framebuffer: &FrameBuffer) -> RgbImage {
+        let (width, height) = (framebuffer.width(), framebuffer.height());
+        let features: Vec<Features> = (0..height)
+            .flat_map(|j| (0..width).map(move |i| framebuffer.features(i, j)))
+            .collect();
+        let hits = || features.iter().filter(|f| f.depth.is_finite());
+        let near = hits().map(|f| f.depth).fold(f64::INFINITY, f64::min);
+        let far = hits().map(|f| f.depth).fold(0.0, f64::max);
+        let mut low = [f64::INFINITY; 3];
+        let mut high = [f64::NEG_INFINITY; 3];
+        for f in hits() {
+            for c in 0..3 {
+                low[c] = low[c].min(f.position[c]);
+                high[c] = high[c].max(f.position[c]);
+            }
+        }
+
+        let byte = |x: f64| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        RgbImage::from_fn(width, height, |i, j| {
+            let f = &features[(j * width + i) as usize];
+            if !f.depth.is_finite() && *self != Aov::Albedo {
+                return Rgb([0, 0, 0]);
+            }
+            let rgb = match self {
+                Aov::Depth if far > near => [(far - f.depth) / (far - near); 3],
+                Aov::Depth => [1.0; 3],
+                Aov::Normal => [0, 1, 2].map(|c| f.normal[c] * 0.5 + 0.5),
+                // gamma 2, like the default transfer of the beauty image
+                Aov::Albedo => [0, 1, 2].map(|c| f.albedo[c].max(0.0).sqrt()),
+                Aov::Uv => [f.uv[0], f.uv[1], 0.0],
+                Aov::Position => [0, 1, 2].map(|c| {
+                    let extent = high[c] - low[c];
+                    if extent > 0.0 {
+                        (f.position[c] - low[c]) / extent
+                    } else {
+                        0.5
+                    }
+                }),
+                Aov::ObjectId => {
+                    let h = rng::hash(&[f.object_id as u64]);
+                    return Rgb([h as u8, (h >> 8) as u8, (h >> 16) as u8]);
+                }
+            };
+            Rgb(rgb.map(byte))
+        })
+    }
+}
@@ -0,0 +1,139 @@
+use crate::rng::SampleRng;
+use crate::rtw_stb_image::RTWImage;
+use crate::vec3::random_in_unit_disk;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// The shape of the lens opening, which out of focus highlights take on
+#[derive(Clone, Debug, Default)]
+pub enum Aperture {
+    #[default]
+    Disk,
+    // a regular polygon of `blades` sides in the disk, its first corner at
+    // the top turned by `rotation` degrees
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    // a disk with the middle `obstruction` of its radius blocked by the
+    // secondary mirror, which makes ring shaped highlights
+    Catadioptric {
+        obstruction: f64,
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    // A point of the opening, within the unit disk or for a mask the square
+    // around it. Both of the lens dimensions are drawn whatever the shape.
+    pub fn sample(&self, rng: &mut SampleRng) -> (f64, f64) {
+        match self {
+            Aperture::Disk => {
+                let p = random_in_unit_disk(rng);
+                (p.x, p.y)
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // a triangle between the middle and one side, and a point in it
+                let (u, v) = rng.get_2d();
+                let n = *blades as f64;
+                let side = (u * n).floor().min(n - 1.0);
+                let along = u * n - side;
+                let corner = |k: f64| {
+                    let angle = PI / 2.0 + rotation.to_radians() + 2.0 * PI * k / n;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(side), corner(side + 1.0));
+                let scale = v.sqrt();
+                (
+                    scale * (a.0 + (b.0 - a.0) * along),
+                    scale * (a.1 + (b.1 - a.1) * along),
+                )
+            }
+            Aperture::Catadioptric { obstruction } => {
+                let (u, v) = rng.get_2d();
+                let inner = obstruction * obstruction;
+                let r = (inner + u * (1.0 - inner)).sqrt();
+                let theta = 2.0 * PI * v;
+                (r * theta.cos(), r * theta.sin())
+            }
+            Aperture::Mask(mask) => {
+                let (u, v) = rng.get_2d();
+                mask.sample(u, v)
+            }
+        }
+    }
+}
+
+// A grayscale image of the opening, stretched over the square around the
+// aperture disk. Points are drawn in proportion to its brightness, a row by
+// the sums of the rows, then a pixel in it.
+#[derive(Debug)]
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // running sums over the rows, from 0 to 1
+    rows: Vec<f64>,
+    // running sums within every row, from 0 to 1, `width + 1` per row
+    columns: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn load(file: &str) -> Result<Self, String> {
+        let image = RTWImage::new(file);
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if width == 0 || height == 0 {
+            return Err(format!("Cannot read the aperture image '{}'", file));
+        }
+        let mut rows = vec![0.0];
+        let mut columns = Vec::with_capacity(height * (width + 1));
+        for y in 0..height {
+            let mut sum = 0.0;
+            columns.push(0.0);
+            for x in 0..width {
+                let [r, g, b] = image.pixel_data(x as u32, y as u32);
+                sum += 0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64;
+                columns.push(sum);
+            }
+            let row = &mut columns[y * (width + 1)..];
+            if sum > 0.0 {
+                row[..=width].iter_mut().for_each(|c| *c /= sum);
+            }
+            rows.push(rows[y] + sum);
+        }
+        let total = rows[height];
+        if total <= 0.0 {
+            return Err(format!("The aperture image '{}' is all black", file));
+        }
+        rows.iter_mut().for_each(|r| *r /= total);
+        Ok(Self {
+            width,
+            height,
+            rows,
+            columns,
+        })
+    }
+    fn sample(&self, u: f64, v: f64) -> (f64, f64) {
+        let (y, fy) = pick(&self.rows, u);
+        let row = &self.columns[y * (self.width + 1)..(y + 1) * (self.width + 1)];
+        let (x, fx) = pick(row, v);
+        // image rows run down, the aperture's y up
+        (
+            2.0 * (x as f64 + fx) / self.width as f64 - 1.0,
+            1.0 - 2.0 * (y as f64 + fy) / self.height as f64,
+        )
+    }
+}
+
+// The bin of running sums `cdf` that `u` falls into, and where in it, so
+// stratified numbers stay stratified
+fn pick(cdf: &[f64], u: f64) -> (usize, f64) {
+    let bins = cdf.len() - 1;
+    let k = (cdf.partition_point(|&c| c <= u).max(1) - 1).min(bins - 1);
+    let size = cdf[k + 1] - cdf[k];
+    let f = if size > 0.0 {
+        ((u - cdf[k]) / size).clamp(0.0, 1.0)
+    } else {
+        0.5
+    };
+    (k, f)
+}
@@ -6,7 +6,7 @@ use crate::ray::Ray;
 use std::cmp::Ordering;
 use std::cmp::Ordering::{Greater, Less};
 use std::sync::Arc;
-use rand::{Rng, thread_rng};
+use crate::rng::SampleRng;
 use crate::vec3::{Point3, Vec3};
 
 #[derive(Clone)]
@@ -81,12 +81,12 @@ fn box_z_compare(a: &Arc<dyn Hittable>, b: &Arc<dyn Hittable>) -> Ordering {
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
         if !self.bbox.hit(r, ray_t.clone()) {
             return None;
         };
-        if let Some(hit_left) = self.left.hit(r, ray_t.clone()) {
-            if let Some(hit_right) = self.right.hit(r, ray_t) {
+        if let Some(hit_left) = self.left.hit(r, ray_t.clone(), rng) {
+            if let Some(hit_right) = self.right.hit(r, ray_t, rng) {
                 if hit_left.t < hit_right.t {
                     Some(hit_left)
                 } else {
@@ -96,7 +96,7 @@ impl Hittable for BvhNode {
                 Some(hit_left)
             }
         } else {
-            self.right.hit(r, ray_t)
+            self.right.hit(r, ray_t, rng)
         }
     }
 
@@ -109,12 +109,11 @@ impl Hittable for BvhNode {
             0.5 * self.right.pdf_value(origin, direction)
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
-        let s = thread_rng().gen_range(0..2);
-        if s == 0 {
-            self.left.random(origin)
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        if rng.get_1d() < 0.5 {
+            self.left.random(origin, rng)
         } else{
-            self.right.random(origin)
+            self.right.random(origin, rng)
         }
     }
 }
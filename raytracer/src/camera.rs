@@ -1,16 +1,28 @@
+use crate::adaptive;
+use crate::aperture::Aperture;
 use crate::color::Color;
+use crate::framebuffer::{Features, FrameBuffer};
 use crate::hittable::Hittable;
 use crate::hittable_list::HittableList;
 use crate::interval::Interval;
+use crate::lens::LensEffects;
 use crate::pdf::{HittablePDF, MixturePDF, Pdf};
+use crate::physical::PhysicalCamera;
+use crate::projection::Projection;
 use crate::ray::Ray;
-use crate::vec3::{cross, random_in_unit_disk, unit_vector, Point3, Vec3};
-use image::RgbImage; // ImageBuffer
+use crate::rng::{self, SampleRng};
+use crate::sampler::{self, Sampler, SamplerKind};
+use crate::shutter::Shutter;
+use crate::stereo::Stereo;
+use crate::tile::{self, Crop, Tile, TILE_SIZE};
+use crate::tonemap::ToneMapping;
+use crate::vec3::{cross, dot, unit_vector, Point3, Vec3};
+use image::RgbImage;
 use indicatif::ProgressBar;
-use rand::{thread_rng, Rng};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use rand::Rng;
+use std::sync::Arc;
 
+#[derive(Clone)]
 pub struct ImageSettings {
     pub aspect_ratio: f64,
     pub image_width: u32,
@@ -18,8 +30,43 @@ pub struct ImageSettings {
     pub samples_per_pixel: u32,
     pub max_depth: i32,
     pub background: Color,
+    // 0 renders on every core
+    pub threads: u32,
+    // the same seed and scene always give the same image
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    // adaptive sampling stops a pixel once its relative error drops below
+    // this, 0 gives every pixel the same number of samples
+    pub noise_threshold: f64,
+    // render only this part of the image, the rest stays black
+    pub crop: Option<Crop>,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            quality: 100,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            background: Color::black(),
+            threads: 0,
+            seed: 0,
+            sampler: SamplerKind::Stratified,
+            noise_threshold: 0.0,
+            crop: None,
+        }
+    }
+}
+
+impl ImageSettings {
+    pub fn image_height(&self) -> u32 {
+        ((self.image_width as f64 / self.aspect_ratio) as u32).max(1)
+    }
 }
 
+#[derive(Clone)]
 pub struct CameraSettings {
     pub vfov: f64,
     pub look_from: Point3,
@@ -27,6 +74,34 @@ pub struct CameraSettings {
     pub vup: Vec3,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub shutter: Shutter,
+    pub projection: Projection,
+    pub stereo: Option<Stereo>,
+    // sets the field of view, the depth of field and the exposure instead
+    pub physical: Option<PhysicalCamera>,
+    pub aperture: Aperture,
+    // distortion, vignetting and chromatic aberration, left out of panoramas
+    pub lens: Option<LensEffects>,
+}
+
+// a pinhole looking down -z
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            vfov: 40.0,
+            look_from: Point3::new(0.0, 0.0, 0.0),
+            look_at: Point3::new(0.0, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            shutter: Shutter::default(),
+            projection: Projection::default(),
+            stereo: None,
+            physical: None,
+            aperture: Aperture::default(),
+            lens: None,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -37,12 +112,20 @@ pub struct Camera {
     pub image_height: u32,
     pub quality: u8,
     pub samples_per_pixel: u32,
-    pub pixel_samples_scale: f64,
-    pub sqrt_spp: u32,
-    pub recip_sqrt_spp: f64,
     pub max_depth: i32,
     pub background: Color,
-    pub img: RgbImage,
+    pub threads: u32,
+    pub seed: u64,
+    pub sampler: Arc<dyn Sampler>,
+    pub noise_threshold: f64,
+    // the pixels that are rendered, all of them without a crop
+    pub window: Tile,
+    // the whole image, or only the window for the jobs of a distributed render
+    pub framebuffer: FrameBuffer,
+    // also record the first hit features of every sample into the framebuffer
+    pub features: bool,
+    // index of the first sample of every pixel, for renders split by samples
+    pub first_sample: u32,
     // Camera
     pub camera_center: Point3,
     pub vfov: f64,
@@ -53,6 +136,20 @@ pub struct Camera {
     pub h: f64,
     pub defocus_angle: f64,
     pub focus_dist: f64,
+    pub aperture: Aperture,
+    pub shutter: Shutter,
+    pub projection: Projection,
+    pub stereo: Option<Stereo>,
+    pub lens: Option<LensEffects>,
+    // what the radiance is multiplied by, 1 unless the camera is physical
+    pub exposure: f64,
+    // the size of one eye's view in a stereo image, otherwise of the image
+    pub view_width: u32,
+    pub view_height: u32,
+    // right, up and backwards
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
     // Viewport
     pub viewport_height: f64,
     pub viewport_width: f64,
@@ -68,6 +165,7 @@ pub struct Camera {
 
 impl Camera {
     pub fn new(image_settings: ImageSettings, camera_settings: CameraSettings) -> Self {
+        let image_height = image_settings.image_height();
         let ImageSettings {
             aspect_ratio,
             image_width,
@@ -75,28 +173,55 @@ impl Camera {
             samples_per_pixel,
             max_depth,
             background,
+            threads,
+            seed,
+            sampler,
+            noise_threshold,
+            crop,
         } = image_settings;
 
         let CameraSettings {
-            vfov,
+            mut vfov,
             look_from,
             look_at,
             vup,
-            defocus_angle,
+            mut defocus_angle,
             focus_dist,
+            shutter,
+            projection,
+            stereo,
+            physical,
+            aperture,
+            lens,
         } = camera_settings;
-        let mut image_height: u32 = (image_width as f64 / aspect_ratio) as u32;
-        if image_height == 0 {
-            image_height = 1;
+        // scene files report this as an error, built scenes get here
+        if let (Some(_), Err(message)) = (&stereo, Stereo::check(&projection)) {
+            panic!("{}", message);
         }
         let camera_center: Point3 = look_from;
+        let (view_width, view_height) = match &stereo {
+            Some(stereo) => stereo.view_size(image_width, image_height),
+            None => (image_width, image_height),
+        };
+        let ratio = view_width as f64 / view_height as f64;
+        if let Some(physical) = &physical {
+            vfov = physical.vfov(ratio);
+            defocus_angle = 2.0
+                * (physical.aperture_radius() / focus_dist)
+                    .atan()
+                    .to_degrees();
+        }
         let theta: f64 = vfov * std::f64::consts::PI / 180.0;
         let h: f64 = f64::tan(theta / 2.0);
-        let sqrt_spp = (samples_per_pixel as f64).sqrt() as u32;
-        let pixel_samples_scale: f64 = 1.0 / (sqrt_spp * sqrt_spp) as f64;
-        let recip_sqrt_spp = 1.0 / sqrt_spp as f64;
-        let viewport_height: f64 = 2.0 * h * focus_dist;
-        let viewport_width: f64 = viewport_height * (image_width as f64 / image_height as f64);
+        let mut viewport_height: f64 = 2.0 * h * focus_dist;
+        let mut viewport_width: f64 = viewport_height * ratio;
+        // an orthographic view lies in the focus plane too, by default as
+        // large as the perspective one would be there
+        if let Projection::Orthographic { width, height } = projection {
+            viewport_height =
+                height.unwrap_or_else(|| width.map_or(viewport_height, |w| w / ratio));
+            viewport_width = width.unwrap_or(viewport_height * ratio);
+        }
         // edge vector
         let w = unit_vector(&(look_from - look_at));
         let u = unit_vector(&cross(&vup, &w));
@@ -104,8 +229,8 @@ impl Camera {
         let viewport_u: Vec3 = u * viewport_width;
         let viewport_v: Vec3 = v * -viewport_height;
         // delta vector
-        let pixel_delta_u: Vec3 = viewport_u / image_width as f64;
-        let pixel_delta_v: Vec3 = viewport_v / image_height as f64;
+        let pixel_delta_u: Vec3 = viewport_u / view_width as f64;
+        let pixel_delta_v: Vec3 = viewport_v / view_height as f64;
         // upper left
         let viewport_upper_left: Point3 =
             camera_center - w * focus_dist - viewport_u / 2.0 - viewport_v / 2.0;
@@ -121,12 +246,28 @@ impl Camera {
             image_height,
             quality,
             samples_per_pixel,
-            pixel_samples_scale,
-            sqrt_spp,
-            recip_sqrt_spp,
             max_depth,
             background,
-            img: RgbImage::new(image_width, image_height),
+            threads: if threads == 0 {
+                tile::default_threads()
+            } else {
+                threads
+            },
+            seed,
+            sampler: sampler::new(sampler, samples_per_pixel, seed),
+            noise_threshold,
+            window: match crop {
+                Some(crop) => crop.window(image_width, image_height),
+                None => Tile {
+                    x: 0,
+                    y: 0,
+                    width: image_width,
+                    height: image_height,
+                },
+            },
+            framebuffer: FrameBuffer::new(image_width, image_height),
+            features: false,
+            first_sample: 0,
             camera_center,
             look_from,
             look_at,
@@ -136,6 +277,17 @@ impl Camera {
             h,
             defocus_angle,
             focus_dist,
+            aperture,
+            shutter,
+            projection,
+            stereo,
+            lens,
+            exposure: physical.map_or(1.0, |physical| physical.exposure()),
+            view_width,
+            view_height,
+            u,
+            v,
+            w,
             viewport_height,
             viewport_width,
             viewport_u,
@@ -149,208 +301,368 @@ impl Camera {
         }
     }
 
-    pub fn render(&mut self, world: HittableList, lights: Arc<dyn Hittable>) {
+    // quantize the accumulated linear radiance into an 8-bit image
+    pub fn image(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        self.framebuffer.to_rgb_image(tone_mapping)
+    }
+
+    pub fn render(&mut self, world: HittableList, lights: Option<Arc<dyn Hittable>>) {
+        self.render_pass(&world, &lights, self.samples_per_pixel);
+    }
+
+    // Adds `samples` samples per pixel (on average, with adaptive sampling) to
+    // the framebuffer. Every pixel continues its own sample indices, so a
+    // render in several passes matches one done in a single pass.
+    pub fn render_pass(
+        &mut self,
+        world: &HittableList,
+        lights: &Option<Arc<dyn Hittable>>,
+        samples: u32,
+    ) {
         let progress = if option_env!("CI").unwrap_or_default() == "true" {
             ProgressBar::hidden()
         } else {
-            ProgressBar::new((self.image_height * self.image_width) as u64)
+            ProgressBar::new(self.window.pixels() as u64)
         };
 
-        // let lines: Vec<Option<Vec<Color>>> = vec![None; self.image_height as usize];
-        // let lines = Arc::new(Mutex::new(lines));
-        let img = Arc::new(Mutex::new(self.img.clone()));
-        let progress = Arc::new(Mutex::new(progress));
-
-        let mut rend_lines = vec![];
-
-        let image_height = self.image_height;
-        let image_width = self.image_width;
-
-        for n in 0..20 {
-            let progress = Arc::clone(&progress);
-            let img = Arc::clone(&img);
-            let world = world.clone();
-            let lights = lights.clone();
-            let copy = Sensor::new(self);
-            let rend_line = thread::spawn(move || {
-                for j in (0..image_height).rev() {
-                    for i in 0..image_width {
-                        if (i + j) % 20 != n {
-                            continue;
-                        }
-
-                        let mut pixel_color: Color = Color::new(0.0, 0.0, 0.0);
-
-                        for s_j in 0..copy.sqrt_spp {
-                            for s_i in 0..copy.sqrt_spp {
-                                let r = copy.get_ray(i, j, s_i, s_j);
-                                pixel_color +=
-                                    copy.ray_color(&r, copy.max_depth, &world, lights.clone());
-                            }
-                        }
-                        pixel_color *= copy.pixel_samples_scale;
-
-                        let mut img = img.lock().unwrap();
-                        let pixel = img.get_pixel_mut(i, j);
-                        *pixel = pixel_color.write_color();
-                        drop(img);
-
-                        let progress = progress.lock().unwrap();
-                        progress.inc(1);
-                        drop(progress);
+        // features can only be added from the first sample on
+        if self.features
+            && !self.framebuffer.has_features()
+            && self.framebuffer.total_samples() == 0
+        {
+            self.framebuffer =
+                FrameBuffer::with_features(self.framebuffer.width(), self.framebuffer.height());
+        }
+        // where the top left pixel of the framebuffer lies in the image
+        let (x0, y0) = if self.framebuffer.width() == self.image_width
+            && self.framebuffer.height() == self.image_height
+        {
+            (0, 0)
+        } else {
+            (self.window.x, self.window.y)
+        };
+        let sensor = Sensor::new(self);
+        let seed = self.seed;
+        let first_sample = self.first_sample;
+        let tiles = tile::split_window(self.image_width, self.image_height, TILE_SIZE, self.window);
+        // every tile is rendered into its own copy of its pixels and written back at the end
+        let rendered = tile::render_tiles(&tiles, self.threads, |tile| {
+            let mut buffer =
+                self.framebuffer
+                    .crop(tile.x - x0, tile.y - y0, tile.width, tile.height);
+            // adds `count` samples to a pixel, continuing its sample indices
+            let sample = |buffer: &mut FrameBuffer, i: u32, j: u32, count: u32| {
+                let (x, y) = (tile.x + i, tile.y + j);
+                let first = first_sample + buffer.samples(i, j);
+                for s in first..first + count {
+                    let mut rng = rng::sample_rng(&self.sampler, seed, x, y, s);
+                    let (color, features) = sensor.sample(x, y, world, lights, &mut rng);
+                    buffer.add_sample(i, j, color * sensor.exposure);
+                    buffer.add_features(i, j, &features);
+                }
+            };
+            if self.noise_threshold > 0.0 {
+                adaptive::render(&mut buffer, samples, self.noise_threshold, sample);
+            } else {
+                for j in 0..tile.height {
+                    for i in 0..tile.width {
+                        sample(&mut buffer, i, j, samples);
                     }
                 }
-            });
-            rend_lines.push(rend_line);
-        }
+            }
+            progress.inc(tile.pixels() as u64);
+            (tile, buffer)
+        });
 
-        // for j in (0..self.image_height).rev() {
-        //     // let lines_clone = Arc::clone(&lines);
-        //     let progress = Arc::clone(&progress);
-        //     let world = world.clone();
-        //     let lights = lights.clone();
-        //     let copy = Sensor::new(self);
-        //     let rend_line = thread::spawn(move || {
-        //         // let mut line: Vec<Color> = Vec::with_capacity(image_width as usize);
-        //         for i in 0..image_width {
-        //             let mut pixel_color: Color = Color::new(0.0, 0.0, 0.0);
-        //
-        //             for s_j in 0..copy.sqrt_spp {
-        //                 for s_i in 0..copy.sqrt_spp {
-        //                     let r = copy.get_ray(i, j, s_i, s_j);
-        //                     pixel_color +=
-        //                         copy.ray_color(&r, copy.max_depth, &world, lights.clone());
-        //                 }
-        //             }
-        //             pixel_color *= copy.pixel_samples_scale;
-        //
-        //             let mut img = img.lock().unwrap();
-        //             let pixel = img.get_pixel_mut(i, j);
-        //             *pixel = pixel_color.write_color();
-        //             drop(img);
-        //             // line.push(pixel_color);
-        //
-        //             let progress = progress.lock().unwrap();
-        //             progress.inc(1);
-        //             drop(progress);
-        //         }
-        //         // let mut lines = lines_clone.lock().unwrap();
-        //         // lines[j as usize] = Some(line);
-        //         // drop(lines);
-        //     });
-        //     rend_lines.push(rend_line);
-        // }
-
-        for rend_line in rend_lines {
-            rend_line.join().unwrap();
+        for (tile, buffer) in rendered.iter() {
+            self.framebuffer.paste(buffer, tile.x - x0, tile.y - y0);
         }
-
-        progress.lock().unwrap().finish();
-
-        // let lines = Arc::try_unwrap(lines).expect("!").into_inner().unwrap();
-        let img = Some(Arc::try_unwrap(img).unwrap().into_inner().unwrap());
-        self.img = img.as_ref().unwrap().clone();
-        // for (j, line_option) in lines.into_iter().enumerate() {
-        //     if let Some(line) = line_option {
-        //         for (i, color) in line.into_iter().enumerate() {
-        //             let pixel = self.img.get_pixel_mut(i as u32, j as u32);
-        //             *pixel = color.write_color();
-        //         }
-        //     }
-        // }
+        progress.finish();
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 struct Sensor {
-    pub pixel_samples_scale: f64,
-    pub sqrt_spp: u32,
-    pub recip_sqrt_spp: f64,
     pub max_depth: i32,
+    pub image_width: u32,
+    pub image_height: u32,
+    pub view_width: f64,
+    pub view_height: f64,
     pub background: Color,
     pub pixel100_loc: Point3,
     pub pixel_delta_u: Vec3,
     pub pixel_delta_v: Vec3,
     pub camera_center: Point3,
+    pub focus_dist: f64,
     pub defocus_angle: f64,
     pub defocus_disk_u: Vec3,
     pub defocus_disk_v: Vec3,
+    pub aperture: Aperture,
+    pub shutter: Shutter,
+    pub projection: Projection,
+    pub stereo: Option<Stereo>,
+    pub lens: Option<LensEffects>,
+    pub exposure: f64,
+    // from the middle of the focus plane back to the camera
+    pub focus_offset: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
 }
 
 impl Sensor {
     pub fn new(camera: &Camera) -> Self {
         Self {
-            pixel_samples_scale: camera.pixel_samples_scale,
-            sqrt_spp: camera.sqrt_spp,
-            recip_sqrt_spp: camera.recip_sqrt_spp,
             max_depth: camera.max_depth,
+            image_width: camera.image_width,
+            image_height: camera.image_height,
+            view_width: camera.view_width as f64,
+            view_height: camera.view_height as f64,
             background: camera.background,
             pixel100_loc: camera.pixel100_loc,
             pixel_delta_u: camera.pixel_delta_u,
             pixel_delta_v: camera.pixel_delta_v,
             camera_center: camera.camera_center,
+            focus_dist: camera.focus_dist,
             defocus_angle: camera.defocus_angle,
             defocus_disk_u: camera.defocus_disk_u,
             defocus_disk_v: camera.defocus_disk_v,
+            aperture: camera.aperture.clone(),
+            shutter: camera.shutter.clone(),
+            projection: camera.projection,
+            stereo: camera.stereo,
+            lens: camera.lens,
+            exposure: camera.exposure,
+            focus_offset: camera.camera_center
+                - (camera.viewport_upper_left + (camera.viewport_u + camera.viewport_v) / 2.0),
+            u: camera.u,
+            v: camera.v,
+            w: camera.w,
         }
     }
-    fn get_ray(&self, i: u32, j: u32, s_i: u32, s_j: u32) -> Ray {
-        let offset = self.sample_square_stratified(s_i, s_j);
-        let pixel_sample = self.pixel100_loc
-            + (self.pixel_delta_u * (i as f64 + offset.x))
-            + (self.pixel_delta_v * (j as f64 + offset.y));
-        let ray_origin = if self.defocus_angle <= 0.0 {
-            self.camera_center
-        } else {
-            self.defocus_disk_sample()
+    // The radiance and first hit features of one sample of pixel (i, j).
+    // With chromatic aberration every channel follows its own ray, all of
+    // them from the same random numbers, and the green one gives the features.
+    fn sample(
+        &self,
+        i: u32,
+        j: u32,
+        world: &dyn Hittable,
+        lights: &Option<Arc<dyn Hittable>>,
+        rng: &mut SampleRng,
+    ) -> (Color, Features) {
+        match &self.lens {
+            Some(lens) if lens.chromatic_aberration != 0.0 && self.viewport() => {
+                let mut color = Color::black();
+                let mut features = Features::miss(Color::black());
+                for channel in 0..3 {
+                    let (c, f) = self.trace(
+                        i,
+                        j,
+                        lens.magnification(channel),
+                        world,
+                        lights,
+                        &mut rng.clone(),
+                    );
+                    color[channel] = c[channel];
+                    if channel == 1 {
+                        features = f;
+                    }
+                }
+                (color, features)
+            }
+            _ => self.trace(i, j, 1.0, world, lights, rng),
+        }
+    }
+    fn trace(
+        &self,
+        i: u32,
+        j: u32,
+        magnification: f64,
+        world: &dyn Hittable,
+        lights: &Option<Arc<dyn Hittable>>,
+        rng: &mut SampleRng,
+    ) -> (Color, Features) {
+        match self.get_ray(i, j, magnification, rng) {
+            Some((r, weight)) => {
+                let mut features = Features::miss(self.background);
+                let color = self.ray_color(
+                    &r,
+                    self.max_depth,
+                    world,
+                    lights.clone(),
+                    rng,
+                    Some(&mut features),
+                );
+                (color * weight, features)
+            }
+            // outside of a fisheye circle
+            None => (Color::black(), Features::miss(Color::black())),
+        }
+    }
+    // Reads the camera dimensions in order: pixel offset, lens, time. The
+    // image is `magnification` times as large in the channel being traced.
+    // Gives the ray and how much of its light the lens lets through, None
+    // where the projection does not cover the image.
+    fn get_ray(
+        &self,
+        i: u32,
+        j: u32,
+        magnification: f64,
+        rng: &mut SampleRng,
+    ) -> Option<(Ray, f64)> {
+        let (px, py) = rng.get_2d();
+        let (mut x, mut y) = (i as f64 + px, j as f64 + py);
+        // -1 for the left eye, 1 for the right, and the point in its view
+        let mut eye = 0.0;
+        if let Some(stereo) = &self.stereo {
+            (eye, x, y) = stereo.eye(x, y, self.image_width, self.image_height)?;
+        }
+        let mut pixel_sample =
+            self.pixel100_loc + (self.pixel_delta_u * (x - 0.5)) + (self.pixel_delta_v * (y - 0.5));
+        if let (Some(lens), true) = (&self.lens, self.viewport()) {
+            pixel_sample = self.undistort(lens, pixel_sample, magnification);
+        }
+        // Perspective rays start from the camera, or an eye beside it, and
+        // aim through a point on the focus plane. Orthographic ones start
+        // from right behind their pixel, panoramic ones have no such point.
+        let (center, target) = match (self.projection, &self.stereo) {
+            (Projection::Orthographic { .. }, _) => {
+                (pixel_sample + self.focus_offset, Some(pixel_sample))
+            }
+            (Projection::Perspective, Some(stereo)) => {
+                let center = self.camera_center + self.u * (eye * stereo.interocular / 2.0);
+                // where the middle of the eyes sees the pixel at the convergence distance
+                let meet = self.camera_center
+                    + (pixel_sample - self.camera_center) * (stereo.convergence / self.focus_dist);
+                let target = center + (meet - center) * (self.focus_dist / stereo.convergence);
+                (center, Some(target))
+            }
+            (Projection::Perspective, None) => (self.camera_center, Some(pixel_sample)),
+            _ => (self.camera_center, None),
         };
-        let ray_direction = pixel_sample - ray_origin;
-        let ray_time = thread_rng().gen_range(0.0..1.0);
+        // the lens dimensions are drawn either way to keep the time dimension in place
+        let lens = self.defocus_disk_sample(center, rng);
+        let row = y / self.view_height;
+        let ray_time = self.shutter.time(rng.get_1d(), row);
 
-        Ray::new(&ray_origin, &ray_direction, ray_time)
+        let mut weight = 1.0;
+        let (ray_origin, ray_direction) = match target {
+            Some(target) => {
+                if self.lens.map_or(false, |lens| lens.vignetting) {
+                    // natural vignetting, by the ray through the middle of the lens
+                    let cos = dot(&unit_vector(&(target - center)), &-self.w);
+                    weight = cos.powi(4);
+                }
+                let ray_origin = if self.defocus_angle <= 0.0 {
+                    center
+                } else {
+                    lens
+                };
+                (ray_origin, target - ray_origin)
+            }
+            // panoramas have no lens and so no depth of field
+            None => {
+                let d = self
+                    .projection
+                    .direction(x, y, self.view_width, self.view_height)?;
+                let d = self.u * d.x + self.v * d.y - self.w * d.z;
+                match &self.stereo {
+                    // Omni-directional stereo: the eyes sit on a circle around
+                    // the camera, across every direction, and come together
+                    // towards the poles. Both aim at the convergence distance.
+                    Some(stereo) => {
+                        let offset = cross(&d, &self.v) * (eye * stereo.interocular / 2.0);
+                        (center + offset, d * stereo.convergence - offset)
+                    }
+                    None => (center, d),
+                }
+            }
+        };
+        Some((Ray::new(&ray_origin, &ray_direction, ray_time), weight))
     }
-    fn defocus_disk_sample(&self) -> Point3 {
-        let p = random_in_unit_disk();
-        self.camera_center + (self.defocus_disk_u * p.x) + (self.defocus_disk_v * p.y)
+    // whether the rays go through a viewport, the lens leaves panoramas alone
+    fn viewport(&self) -> bool {
+        matches!(
+            self.projection,
+            Projection::Perspective | Projection::Orthographic { .. }
+        )
     }
-    fn sample_square_stratified(&self, s_i: u32, s_j: u32) -> Vec3 {
-        let px = (s_i as f64 + thread_rng().gen_range(0.0..1.0)) * self.recip_sqrt_spp - 0.5;
-        let py = (s_j as f64 + thread_rng().gen_range(0.0..1.0)) * self.recip_sqrt_spp - 0.5;
-
-        Vec3::new(px, py, 0.0)
+    // The point of the focus plane that the lens images at `pixel_sample`,
+    // radii taken from the middle of the view over the focus distance
+    fn undistort(&self, lens: &LensEffects, pixel_sample: Point3, magnification: f64) -> Point3 {
+        let middle = self.camera_center - self.focus_offset;
+        let offset = pixel_sample - middle;
+        let r = offset.length() / self.focus_dist / magnification;
+        if r <= 0.0 {
+            return pixel_sample;
+        }
+        middle + offset * (lens.undistort(r) / (r * magnification))
+    }
+    fn defocus_disk_sample(&self, center: Point3, rng: &mut SampleRng) -> Point3 {
+        let (x, y) = self.aperture.sample(rng);
+        center + (self.defocus_disk_u * x) + (self.defocus_disk_v * y)
     }
     fn ray_color(
         &self,
         r: &Ray,
         depth: i32,
         world: &dyn Hittable,
-        lights: Arc<dyn Hittable>,
+        lights: Option<Arc<dyn Hittable>>,
+        rng: &mut SampleRng,
+        features: Option<&mut Features>,
     ) -> Color {
         if depth <= 0 {
             return Color::black();
         }
+        rng.start_bounce((self.max_depth - depth) as u32);
 
-        if let Some(hit_record) = world.hit(r, Interval::new(0.001, f64::INFINITY)) {
+        if let Some(hit_record) = world.hit(r, Interval::new(0.001, f64::INFINITY), rng) {
             let color_from_emission =
                 hit_record
                     .mat
                     .emitted(r, &hit_record, hit_record.u, hit_record.v, &hit_record.p);
-            return if let Some(srec) = hit_record.mat.scatter(r, &hit_record) {
+            let srec = hit_record.mat.scatter(r, &hit_record, rng);
+            if let Some(features) = features {
+                // emitters count as their own albedo, clamped like a reflectance
+                let albedo = srec.as_ref().map_or(color_from_emission, |s| s.attenuation);
+                *features = Features {
+                    albedo: Color::new(
+                        albedo.x.clamp(0.0, 1.0),
+                        albedo.y.clamp(0.0, 1.0),
+                        albedo.z.clamp(0.0, 1.0),
+                    ),
+                    normal: hit_record.normal,
+                    depth: hit_record.t * r.direction().length(),
+                    uv: [hit_record.u, hit_record.v],
+                    position: hit_record.p,
+                    object_id: hit_record.object_id,
+                };
+            }
+            return if let Some(srec) = srec {
                 if srec.skip_pdf {
                     if let Some(scattered) = srec.skip_pdf_ray {
                         return srec.attenuation
-                            * self.ray_color(&scattered, depth - 1, world, lights);
+                            * self.ray_color(&scattered, depth - 1, world, lights, rng, None);
                     }
                 }
                 if let Some(pdf_ptr) = srec.pdf_ptr {
-                    let light_ptr = Arc::new(HittablePDF::new(lights.clone(), &hit_record.p));
-                    let p = MixturePDF::new(light_ptr, pdf_ptr);
+                    let p: Arc<dyn Pdf> = match &lights {
+                        Some(lights) => {
+                            let light_ptr =
+                                Arc::new(HittablePDF::new(lights.clone(), &hit_record.p));
+                            Arc::new(MixturePDF::new(light_ptr, pdf_ptr))
+                        }
+                        // nothing to importance sample, fall back to the material alone
+                        None => pdf_ptr,
+                    };
 
-                    let scattered = Ray::new(&hit_record.p, &p.generate(), r.time());
+                    let scattered = Ray::new(&hit_record.p, &p.generate(rng), r.time());
                     let pdf_val = p.value(&scattered.direction());
                     let scattering_pdf = hit_record.mat.scattering_pdf(r, &hit_record, &scattered);
 
-                    let sample_color = self.ray_color(&scattered, depth - 1, world, lights);
+                    let sample_color =
+                        self.ray_color(&scattered, depth - 1, world, lights, rng, None);
                     srec.attenuation * scattering_pdf * sample_color / pdf_val + color_from_emission
                 } else {
                     color_from_emission
@@ -363,7 +675,6 @@ impl Sensor {
     }
 }
 
-fn _sample_square() -> Vec3 {
-    let mut rng = thread_rng();
+fn _sample_square(rng: &mut SampleRng) -> Vec3 {
     Vec3::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), 0.0)
 }
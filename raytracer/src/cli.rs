@@ -0,0 +1,471 @@
+use raytracer::aov::{self, Aov};
+use raytracer::progressive::Progressive;
+use raytracer::sampler::{self, SamplerKind};
+use raytracer::tile::Crop;
+use raytracer::tonemap::{self, Transfer};
+use raytracer::Scene;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const USAGE: &str = "Usage:
+    raytracer render <scene> [options]
+    raytracer render --resume <checkpoint> [options]
+    raytracer worker --listen <address>
+    raytracer list
+    raytracer edge [input] [-o <output>]
+    raytracer help
+
+<scene> is either a built-in scene name (see `list`) or a .json scene file.
+Running without a command renders `final_scene`.
+
+Render options:
+    -o, --output <path>     output image path (default: the scene's own path)
+    -w, --width <pixels>    image width, height follows the scene's aspect ratio
+    -s, --samples <n>       samples per pixel
+    -d, --depth <n>         max ray bounce depth
+    -t, --threads <n>       number of render threads (default: every core)
+    --seed <n>              random seed, the same seed always renders the same image
+    --sampler <name>        independent, stratified, halton, sobol or blue_noise (default: stratified)
+    --adaptive <threshold>  stop sampling a pixel once its relative error is below the threshold
+                            and spend the rest of the samples on noisy pixels, e.g. 0.01
+    --heatmap <path>        also write the number of samples per pixel as a heatmap
+    --denoise               filter the noise out, guided by the albedo, normals and depth
+    --denoise-strength <x>  how much noise the denoiser smooths away (default: 1)
+    --denoise-passes <n>    filter passes, each doubles the radius (default: 5)
+    --keep-noisy            also write the image before denoising, as <output>_noisy
+    --aovs <list>           first hit outputs, comma separated or `all`: depth, normal, albedo, uv,
+                            position, id. Layers of the same file for exr, <output>_<aov> otherwise
+    --aov-files             write the AOVs as separate images for exr too
+    --checkpoint <path>     render in passes and save the progress to a checkpoint file
+    --checkpoint-interval <time>
+                            how often the checkpoint is saved (default: 1m)
+    --resume <checkpoint>   continue a checkpointed render, the scene and its settings come from the file
+    --pass-samples <n>      samples per pixel of every pass (default: a sixteenth of them)
+    --passes <n>            stop after this many passes
+    --time-limit <time>     stop before a pass would end after this, e.g. 90s, 10m or 2h
+    --budget <time>         render for this long instead of to a number of samples per pixel
+    --crop <x,y,w,h>        render only this rectangle, in pixels, or as fractions of the image
+                            when the numbers have a decimal point, e.g. 0.25,0.25,0.5,0.5
+    --full-frame            write a cropped render at the full image size, black around the crop
+    --frames <first>-<last> render the frames of the scene's camera animation, numbered after the
+                            run of # in the output path (image_####.png) or added to its name
+    --workers <addresses>   render on worker processes instead, comma separated host:port or
+                            unix:<path> addresses of `raytracer worker`s
+    -f, --format <format>   jpg, png, png16, ppm, hdr, pfm or exr (default: from the output extension)
+    --tonemap <operator>    clamp, reinhard, aces or hable (default: clamp)
+    --white <radiance>      radiance mapped to pure white by reinhard and hable
+    --exposure <stops>      exposure compensation before tone mapping
+    --transfer <function>   gamma2, srgb or linear (default: gamma2)
+
+Worker options:
+    -l, --listen <address>  host:port or unix:<path> to wait for a coordinator's jobs on
+                            (default: 127.0.0.1:7878). Scene files are sent along, the models
+                            and textures they use have to be found from the worker's directory";
+
+pub enum Command {
+    Render(Box<RenderOptions>),
+    List,
+    Edge { input: String, output: String },
+    Worker { listen: String },
+    Help,
+}
+
+pub struct RenderOptions {
+    pub scene: String,
+    pub output: Option<String>,
+    pub width: Option<u32>,
+    pub samples: Option<u32>,
+    pub depth: Option<i32>,
+    pub threads: Option<u32>,
+    pub seed: Option<u64>,
+    pub sampler: Option<SamplerKind>,
+    pub noise_threshold: Option<f64>,
+    pub heatmap: Option<String>,
+    pub denoise: bool,
+    pub denoise_strength: Option<f64>,
+    pub denoise_passes: Option<u32>,
+    pub keep_noisy: bool,
+    pub aovs: Vec<Aov>,
+    pub aov_files: bool,
+    pub checkpoint: Option<String>,
+    pub checkpoint_interval: Option<Duration>,
+    pub resume: Option<String>,
+    pub pass_samples: Option<u32>,
+    pub passes: Option<u32>,
+    pub time_limit: Option<Duration>,
+    pub budget: Option<Duration>,
+    pub crop: Option<Crop>,
+    pub full_frame: bool,
+    pub frames: Option<(u32, u32)>,
+    pub workers: Vec<String>,
+    pub format: Option<String>,
+    pub tonemap: Option<String>,
+    pub white: Option<f64>,
+    pub exposure: Option<f64>,
+    pub transfer: Option<Transfer>,
+}
+
+impl RenderOptions {
+    pub fn new(scene: &str) -> Self {
+        Self {
+            scene: String::from(scene),
+            output: None,
+            width: None,
+            samples: None,
+            depth: None,
+            threads: None,
+            seed: None,
+            sampler: None,
+            noise_threshold: None,
+            heatmap: None,
+            denoise: false,
+            denoise_strength: None,
+            denoise_passes: None,
+            keep_noisy: false,
+            aovs: vec![],
+            aov_files: false,
+            checkpoint: None,
+            checkpoint_interval: None,
+            resume: None,
+            pass_samples: None,
+            passes: None,
+            time_limit: None,
+            budget: None,
+            crop: None,
+            full_frame: false,
+            frames: None,
+            workers: vec![],
+            format: None,
+            tonemap: None,
+            white: None,
+            exposure: None,
+            transfer: None,
+        }
+    }
+    pub fn apply(&self, scene: &mut Scene) {
+        if let Some(output) = &self.output {
+            scene.output = output.clone();
+        }
+        if let Some(width) = self.width {
+            scene.image_settings.image_width = width;
+        }
+        if let Some(samples) = self.samples {
+            scene.image_settings.samples_per_pixel = samples;
+        }
+        if let Some(depth) = self.depth {
+            scene.image_settings.max_depth = depth;
+        }
+        if let Some(threads) = self.threads {
+            scene.image_settings.threads = threads;
+        }
+        if let Some(seed) = self.seed {
+            scene.image_settings.seed = seed;
+        }
+        if let Some(sampler) = self.sampler {
+            scene.image_settings.sampler = sampler;
+        }
+        if let Some(crop) = self.crop {
+            scene.image_settings.crop = Some(crop);
+        }
+        if let Some(threshold) = self.noise_threshold {
+            scene.image_settings.noise_threshold = threshold;
+        }
+        if self.denoise || self.denoise_strength.is_some() || self.denoise_passes.is_some() {
+            let mut denoise = scene.denoise.unwrap_or_default();
+            if let Some(strength) = self.denoise_strength {
+                denoise.strength = strength;
+            }
+            if let Some(passes) = self.denoise_passes {
+                denoise.passes = passes;
+            }
+            scene.denoise = Some(denoise);
+        }
+        for aov in self.aovs.iter() {
+            if !scene.aovs.contains(aov) {
+                scene.aovs.push(*aov);
+            }
+        }
+        if let Some(name) = &self.tonemap {
+            scene.tone_mapping.operator = tonemap::operator(name, self.white).unwrap();
+        }
+        if let Some(exposure) = self.exposure {
+            scene.tone_mapping.exposure = exposure;
+        }
+        if let Some(transfer) = self.transfer {
+            scene.tone_mapping.transfer = transfer;
+        }
+    }
+    // a single pass unless one of the progressive options asks for more
+    pub fn progressive(&self, samples_per_pixel: u32) -> Progressive {
+        let mut progressive = Progressive::single_pass(samples_per_pixel);
+        progressive.checkpoint = self
+            .checkpoint
+            .as_ref()
+            .or(self.resume.as_ref())
+            .map(PathBuf::from);
+        let passes =
+            progressive.checkpoint.is_some() || self.passes.is_some() || self.time_limit.is_some();
+        progressive.pass_samples = match self.pass_samples {
+            Some(samples) => samples,
+            // a first pass to see how long a sample takes
+            None if self.budget.is_some() => 1,
+            None if passes => (samples_per_pixel + 15) / 16,
+            None => samples_per_pixel,
+        };
+        progressive.max_passes = self.passes;
+        progressive.time_limit = self.time_limit;
+        progressive.budget = self.budget;
+        if let Some(interval) = self.checkpoint_interval {
+            progressive.checkpoint_interval = interval;
+        }
+        progressive
+    }
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let command = match args.next() {
+        None => return Ok(Command::Render(Box::new(RenderOptions::new("final_scene")))),
+        Some(command) => command,
+    };
+    match command.as_str() {
+        "render" => parse_render(args),
+        "list" => Ok(Command::List),
+        "edge" => parse_edge(args),
+        "worker" => parse_worker(args),
+        "help" | "-h" | "--help" => Ok(Command::Help),
+        _ => Err(format!("Unknown command '{}'", command)),
+    }
+}
+
+fn parse_render<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut scene = None;
+    let mut options = RenderOptions::new("");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => options.output = Some(value(&arg, args.next())?),
+            "-w" | "--width" => options.width = Some(number(&arg, args.next())?),
+            "-s" | "--samples" => options.samples = Some(number(&arg, args.next())?),
+            "-d" | "--depth" => options.depth = Some(number(&arg, args.next())?),
+            "-t" | "--threads" => options.threads = Some(number(&arg, args.next())?),
+            "--seed" => options.seed = Some(number(&arg, args.next())?),
+            "--sampler" => {
+                let name = value(&arg, args.next())?;
+                options.sampler = Some(SamplerKind::from_name(&name).ok_or(format!(
+                    "Unknown sampler '{}', use {}",
+                    name,
+                    sampler::SAMPLERS.join(", ")
+                ))?);
+            }
+            "--adaptive" => options.noise_threshold = Some(number(&arg, args.next())?),
+            "--heatmap" => options.heatmap = Some(value(&arg, args.next())?),
+            "--denoise" => options.denoise = true,
+            "--denoise-strength" => options.denoise_strength = Some(number(&arg, args.next())?),
+            "--denoise-passes" => options.denoise_passes = Some(number(&arg, args.next())?),
+            "--keep-noisy" => options.keep_noisy = true,
+            "--aovs" => options.aovs = aovs(&value(&arg, args.next())?)?,
+            "--aov-files" => options.aov_files = true,
+            "--checkpoint" => options.checkpoint = Some(value(&arg, args.next())?),
+            "--checkpoint-interval" => {
+                options.checkpoint_interval = Some(duration(&arg, args.next())?)
+            }
+            "--resume" => options.resume = Some(value(&arg, args.next())?),
+            "--pass-samples" => options.pass_samples = Some(number(&arg, args.next())?),
+            "--passes" => options.passes = Some(number(&arg, args.next())?),
+            "--time-limit" => options.time_limit = Some(duration(&arg, args.next())?),
+            "--budget" => options.budget = Some(duration(&arg, args.next())?),
+            "--crop" => options.crop = Some(crop(&value(&arg, args.next())?)?),
+            "--full-frame" => options.full_frame = true,
+            "--frames" => options.frames = Some(frames(&value(&arg, args.next())?)?),
+            "--workers" => {
+                options.workers = value(&arg, args.next())?
+                    .split(',')
+                    .map(|address| String::from(address.trim()))
+                    .filter(|address| !address.is_empty())
+                    .collect()
+            }
+            "-f" | "--format" => options.format = Some(value(&arg, args.next())?),
+            "--tonemap" => {
+                let name = value(&arg, args.next())?;
+                if tonemap::operator(&name, None).is_none() {
+                    return Err(format!(
+                        "Unknown tone mapping operator '{}', use {}",
+                        name,
+                        tonemap::OPERATORS.join(", ")
+                    ));
+                }
+                options.tonemap = Some(name);
+            }
+            "--white" => options.white = Some(number(&arg, args.next())?),
+            "--exposure" => options.exposure = Some(number(&arg, args.next())?),
+            "--transfer" => {
+                let name = value(&arg, args.next())?;
+                options.transfer = Some(
+                    Transfer::from_name(&name)
+                        .ok_or(format!("Unknown transfer function '{}'", name))?,
+                );
+            }
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+            _ if scene.is_none() => scene = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    // a resumed render takes its scene from the checkpoint
+    options.scene = match scene {
+        Some(scene) => scene,
+        None if options.resume.is_some() => String::new(),
+        None => return Err(String::from("Missing scene name for `render`")),
+    };
+    if options.width == Some(0) || options.samples == Some(0) || options.threads == Some(0) {
+        return Err(String::from("Width, samples and threads must be positive"));
+    }
+    if options.pass_samples == Some(0) || options.passes == Some(0) {
+        return Err(String::from("Passes and samples per pass must be positive"));
+    }
+    let progressive = options.checkpoint.is_some()
+        || options.resume.is_some()
+        || options.pass_samples.is_some()
+        || options.passes.is_some()
+        || options.time_limit.is_some()
+        || options.budget.is_some();
+    if options.frames.is_some() && (options.checkpoint.is_some() || options.resume.is_some()) {
+        return Err(String::from("Frame sequences cannot be checkpointed"));
+    }
+    if !options.workers.is_empty() && progressive {
+        return Err(String::from(
+            "Renders on workers cannot be split into passes or checkpointed",
+        ));
+    }
+    if options.noise_threshold.map_or(false, |t| t <= 0.0) {
+        return Err(String::from("The noise threshold must be positive"));
+    }
+    if options.denoise_strength.map_or(false, |s| s < 0.0) {
+        return Err(String::from("The denoise strength cannot be negative"));
+    }
+    if options.white.is_some() && options.tonemap.is_none() {
+        return Err(String::from("--white needs --tonemap reinhard or hable"));
+    }
+    Ok(Command::Render(Box::new(options)))
+}
+
+fn parse_worker<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut listen = String::from("127.0.0.1:7878");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-l" | "--listen" => listen = value(&arg, args.next())?,
+            _ => return Err(format!("Unknown option '{}'", arg)),
+        }
+    }
+    Ok(Command::Worker { listen })
+}
+
+fn parse_edge<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut input = None;
+    let mut output = String::from("output/advanced/image3.jpg");
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = value(&arg, args.next())?,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option '{}'", arg)),
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+    Ok(Command::Edge {
+        input: input.unwrap_or_else(|| String::from("images/Final.jpg")),
+        output,
+    })
+}
+
+// `first-last` or a single frame
+fn frames(range: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Invalid frame range '{}', use first-last", range);
+    let (first, last) = match range.split_once('-') {
+        Some((first, last)) => (first, last),
+        None => (range, range),
+    };
+    let first: u32 = first.trim().parse().map_err(|_| error())?;
+    let last: u32 = last.trim().parse().map_err(|_| error())?;
+    if last < first {
+        return Err(error());
+    }
+    Ok((first, last))
+}
+
+// x, y, width and height, all whole pixels or all fractions
+fn crop(rect: &str) -> Result<Crop, String> {
+    let error = || format!("Invalid crop '{}', use x,y,width,height", rect);
+    let values: Vec<&str> = rect.split(',').map(str::trim).collect();
+    if values.len() != 4 {
+        return Err(error());
+    }
+    if values.iter().all(|v| v.contains('.')) {
+        let v: Vec<f64> = values
+            .iter()
+            .map(|v| v.parse().map_err(|_| error()))
+            .collect::<Result<_, _>>()?;
+        if v.iter().any(|v| !(0.0..=1.0).contains(v)) || v[2] == 0.0 || v[3] == 0.0 {
+            return Err(format!(
+                "The fractions of crop '{}' must be between 0 and 1, the size above 0",
+                rect
+            ));
+        }
+        return Ok(Crop::Normalized {
+            x: v[0],
+            y: v[1],
+            width: v[2],
+            height: v[3],
+        });
+    }
+    let v: Vec<u32> = values
+        .iter()
+        .map(|v| v.parse().map_err(|_| error()))
+        .collect::<Result<_, _>>()?;
+    if v[2] == 0 || v[3] == 0 {
+        return Err(format!("Crop '{}' is empty", rect));
+    }
+    Ok(Crop::Pixels {
+        x: v[0],
+        y: v[1],
+        width: v[2],
+        height: v[3],
+    })
+}
+
+fn aovs(list: &str) -> Result<Vec<Aov>, String> {
+    if list == "all" {
+        return Ok(Aov::all());
+    }
+    list.split(',')
+        .map(|name| {
+            Aov::from_name(name.trim()).ok_or(format!(
+                "Unknown AOV '{}', use {} or all",
+                name,
+                aov::AOVS.join(", ")
+            ))
+        })
+        .collect()
+}
+
+// seconds, or a number followed by s, m or h
+fn duration(flag: &str, arg: Option<String>) -> Result<Duration, String> {
+    let arg = value(flag, arg)?;
+    let (number, unit) = match arg.char_indices().last() {
+        Some((at, 's')) => (&arg[..at], 1.0),
+        Some((at, 'm')) => (&arg[..at], 60.0),
+        Some((at, 'h')) => (&arg[..at], 3600.0),
+        _ => (arg.as_str(), 1.0),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n >= 0.0 && n.is_finite() => Ok(Duration::from_secs_f64(n * unit)),
+        _ => Err(format!("Invalid duration '{}' for '{}'", arg, flag)),
+    }
+}
+
+fn value(flag: &str, arg: Option<String>) -> Result<String, String> {
+    arg.ok_or(format!("Missing value for '{}'", flag))
+}
+
+fn number<T: std::str::FromStr>(flag: &str, arg: Option<String>) -> Result<T, String> {
+    let arg = value(flag, arg)?;
+    arg.parse()
+        .map_err(|_| format!("Invalid value '{}' for '{}'", arg, flag))
+}
@@ -1,30 +1,41 @@
 use crate::interval::Interval;
+use crate::tonemap::ToneMapping;
 use crate::vec3::Vec3;
 
 pub type Color = Vec3;
 
 impl Color {
-    pub fn write_color(&self) -> image::Rgb<u8> {
-        let mut r = self.x;
-        let mut g = self.y;
-        let mut b = self.z;
-
-        if r.is_nan() {
-            r = 0.0;
-        }
-        if g.is_nan() {
-            g = 0.0;
+    // quantize after tone mapping, the top of each range is kept below 1
+    fn display(&self, tone_mapping: &ToneMapping) -> [f64; 3] {
+        let intensity: Interval = Interval::new(0.000, 0.999);
+        let mut rgb = tone_mapping.apply(*self);
+        for c in rgb.iter_mut() {
+            *c = intensity.clamp(*c);
         }
-        if b.is_nan() {
-            b = 0.0;
+        rgb
+    }
+    pub fn write_color(&self, tone_mapping: &ToneMapping) -> image::Rgb<u8> {
+        let [r, g, b] = self.display(tone_mapping);
+        image::Rgb([(r * 256.0) as u8, (g * 256.0) as u8, (b * 256.0) as u8])
+    }
+    // rounded to the nearest of the 16-bit levels, so white reaches 65535
+    pub fn write_color16(&self, tone_mapping: &ToneMapping) -> image::Rgb<u16> {
+        let level = |c: f64| (c.clamp(0.0, 1.0) * 65535.0).round() as u16;
+        let [r, g, b] = tone_mapping.apply(*self);
+        image::Rgb([level(r), level(g), level(b)])
+    }
+    // linear radiance for float formats, NaN samples become black
+    pub fn write_linear(&self) -> image::Rgb<f32> {
+        let mut rgb = [self.x as f32, self.y as f32, self.z as f32];
+        for c in rgb.iter_mut() {
+            if c.is_nan() {
+                *c = 0.0;
+            }
         }
-
-        let intensity: Interval = Interval::new(0.000, 0.999);
-
-        let r = (intensity.clamp(linear_to_gamma(r)) * 256.0) as u8;
-        let g = (intensity.clamp(linear_to_gamma(g)) * 256.0) as u8;
-        let b = (intensity.clamp(linear_to_gamma(b)) * 256.0) as u8;
-        image::Rgb([r, g, b])
+        image::Rgb(rgb)
+    }
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
     }
     pub fn white() -> Color {
         Color {
@@ -41,11 +52,3 @@ impl Color {
         }
     }
 }
-
-fn linear_to_gamma(linear: f64) -> f64 {
-    if linear > 0.0 {
-        f64::sqrt(linear)
-    } else {
-        0.0
-    }
-}
@@ -0,0 +1,174 @@
+use crate::color::Color;
+use crate::framebuffer::{Features, FrameBuffer};
+use crate::vec3::{dot, unit_vector};
+
+// B3 spline, the 5 taps of every à-trous pass
+const KERNEL: [f64; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+// luminance differences are measured in standard deviations of the noise
+const SIGMA_LUMINANCE: f64 = 2.0;
+const NORMAL_POWER: f64 = 128.0;
+// relative depth change per pixel of distance
+const SIGMA_DEPTH: f64 = 0.05;
+const SIGMA_ALBEDO: f64 = 0.2;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Denoise {
+    // scales how much noise is smoothed away, 0 keeps the image as it is
+    pub strength: f64,
+    // every pass doubles the filter radius, 5 passes reach 62 pixels
+    pub passes: u32,
+}
+
+impl Default for Denoise {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            passes: 5,
+        }
+    }
+}
+
+// Channels with almost no albedo are left as they are, dividing by them
+// would only blow up the noise.
+fn divisor(albedo: Color) -> Color {
+    let channel = |c: f64| if c < 0.01 { 1.0 } else { c };
+    Color::new(channel(albedo.x), channel(albedo.y), channel(albedo.z))
+}
+
+fn finite(color: Color) -> Color {
+    let channel = |c: f64| if c.is_finite() { c } else { 0.0 };
+    Color::new(channel(color.x), channel(color.y), channel(color.z))
+}
+
+// how much the features of two pixels `distance` pixels apart say they show
+// the same surface
+fn feature_weight(p: &Features, q: &Features, distance: f64) -> f64 {
+    let normal = match (p.normal.length() > 1e-6, q.normal.length() > 1e-6) {
+        (true, true) => dot(&unit_vector(&p.normal), &unit_vector(&q.normal))
+            .max(0.0)
+            .powf(NORMAL_POWER),
+        (false, false) => 1.0,
+        _ => 0.0,
+    };
+    let depth = match (p.depth.is_finite(), q.depth.is_finite()) {
+        (true, true) => (-(p.depth - q.depth).abs() / (SIGMA_DEPTH * p.depth * distance)).exp(),
+        (false, false) => 1.0,
+        _ => 0.0,
+    };
+    let albedo = (-(p.albedo - q.albedo).length_squared() / (SIGMA_ALBEDO * SIGMA_ALBEDO)).exp();
+    normal * depth * albedo
+}
+
+// The variance of a single pixel is a noisy estimate itself, and zero where
+// every sample happened to come back black, which would keep such pixels from
+// being filtered at all. The luminance weights use it blurred a little.
+fn blur_3x3(values: &[f64], width: i64, height: i64) -> Vec<f64> {
+    const TAPS: [f64; 3] = [0.25, 0.5, 0.25];
+    let mut blurred = vec![0.0; values.len()];
+    for j in 0..height {
+        for i in 0..width {
+            let mut sum = 0.0;
+            let mut sum_weight = 0.0;
+            for (dy, ky) in TAPS.iter().enumerate() {
+                for (dx, kx) in TAPS.iter().enumerate() {
+                    let (x, y) = (i + dx as i64 - 1, j + dy as i64 - 1);
+                    if x >= 0 && y >= 0 && x < width && y < height {
+                        sum += kx * ky * values[(y * width + x) as usize];
+                        sum_weight += kx * ky;
+                    }
+                }
+            }
+            blurred[(j * width + i) as usize] = sum / sum_weight;
+        }
+    }
+    blurred
+}
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010) with the
+// luminance weights of SVGF (Schied et al. 2017). The illumination, that is
+// the radiance divided by the albedo, is blurred with a wider kernel on
+// every pass, while the first hit albedo, normal and depth keep it from
+// crossing edges and the variance of every pixel's mean keeps it from
+// smoothing away more than noise. Luminance differences are compared with
+// the noise of both pixels, so bright and dark pixels accept each other
+// alike and the filter does not darken the image. The albedo is multiplied
+// back at the end, so textures stay sharp. The buffer has to be rendered
+// with features.
+pub fn denoise(framebuffer: &FrameBuffer, settings: &Denoise) -> FrameBuffer {
+    assert!(framebuffer.has_features());
+    let (width, height) = (framebuffer.width(), framebuffer.height());
+    let mut features = Vec::with_capacity((width * height) as usize);
+    let mut divisors = vec![];
+    let mut illumination = vec![];
+    let mut variance = vec![];
+    for j in 0..height {
+        for i in 0..width {
+            let f = framebuffer.features(i, j);
+            let d = divisor(f.albedo);
+            let samples = framebuffer.samples(i, j).max(1) as f64;
+            features.push(f);
+            divisors.push(d);
+            let color = finite(framebuffer.pixel(i, j));
+            illumination.push(Color::new(color.x / d.x, color.y / d.y, color.z / d.z));
+            variance.push((framebuffer.variance(i, j) / samples).min(1e6));
+        }
+    }
+
+    for pass in 0..settings.passes {
+        let step = 1i64 << pass;
+        let blurred = blur_3x3(&variance, width as i64, height as i64);
+        let mut filtered = illumination.clone();
+        let mut filtered_variance = variance.clone();
+        for j in 0..height as i64 {
+            for i in 0..width as i64 {
+                let p = (j * width as i64 + i) as usize;
+                let luminance = (illumination[p] * divisors[p]).luminance();
+                let mut sum = Color::black();
+                let mut sum_variance = 0.0;
+                let mut sum_weight = 0.0;
+                for (dy, ky) in KERNEL.iter().enumerate() {
+                    for (dx, kx) in KERNEL.iter().enumerate() {
+                        let (dx, dy) = (dx as i64 - 2, dy as i64 - 2);
+                        let (x, y) = (i + dx * step, j + dy * step);
+                        if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+                            continue;
+                        }
+                        let q = (y * width as i64 + x) as usize;
+                        let weight = if p == q {
+                            kx * ky
+                        } else {
+                            let distance = (step as f64) * ((dx * dx + dy * dy) as f64).sqrt();
+                            let difference =
+                                ((illumination[q] * divisors[q]).luminance() - luminance).abs();
+                            let sigma = SIGMA_LUMINANCE
+                                * settings.strength
+                                * (blurred[p] + blurred[q]).sqrt()
+                                + 1e-6;
+                            kx * ky
+                                * (-difference / sigma).exp()
+                                * feature_weight(&features[p], &features[q], distance)
+                        };
+                        sum += illumination[q] * weight;
+                        sum_variance += weight * weight * variance[q];
+                        sum_weight += weight;
+                    }
+                }
+                filtered[p] = sum / sum_weight;
+                filtered_variance[p] = sum_variance / (sum_weight * sum_weight);
+            }
+        }
+        illumination = filtered;
+        variance = filtered_variance;
+    }
+
+    // the features are kept for the AOV outputs
+    let mut denoised = FrameBuffer::with_features(width, height);
+    for j in 0..height {
+        for i in 0..width {
+            let p = (j * width + i) as usize;
+            denoised.add_sample(i, j, illumination[p] * divisors[p]);
+            denoised.add_features(i, j, &features[p]);
+        }
+    }
+    denoised
+}
@@ -0,0 +1,423 @@
+use crate::camera::Camera;
+use crate::framebuffer::FrameBuffer;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::progressive::{Checkpoint, RenderParams};
+use crate::scene::Scene;
+use crate::tile::{self, Tile};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+const PROTOCOL: &str = "raytracer worker 1";
+// the side of the blocks of pixels handed out as jobs
+const JOB_SIZE: u32 = 64;
+// with fewer blocks than this many per worker, their samples are split too
+const JOBS_PER_WORKER: u32 = 4;
+
+// A piece of a frame for a worker: a block of pixels and a range of their
+// sample indices
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct Job {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub first_sample: u32,
+    pub samples: u32,
+}
+
+// Everything a worker needs to render a job. Built-in scenes are sent by name,
+// scene files as their text, so the workers only need the models and
+// textures they refer to.
+#[derive(Serialize, Deserialize)]
+struct Request {
+    params: RenderParams,
+    source: Option<String>,
+    features: bool,
+    job: Job,
+}
+
+// TCP `host:port` or, on unix, `unix:<path>`
+enum Stream {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    fn connect(address: &str) -> io::Result<Self> {
+        match address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => Ok(Stream::Unix(UnixStream::connect(path)?)),
+            #[cfg(not(unix))]
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unix sockets are not supported here",
+            )),
+            None => Stream::tcp(TcpStream::connect(address)?),
+        }
+    }
+    // jobs are small requests and answers going back and forth, which
+    // Nagle's algorithm would hold back
+    fn tcp(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        Ok(Stream::Tcp(stream))
+    }
+    fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Stream::Tcp(s) => Ok(Stream::Tcp(s.try_clone()?)),
+            #[cfg(unix)]
+            Stream::Unix(s) => Ok(Stream::Unix(s.try_clone()?)),
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(s) => s.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.write(buf),
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(s) => s.flush(),
+            #[cfg(unix)]
+            Stream::Unix(s) => s.flush(),
+        }
+    }
+}
+
+fn read_line<R: BufRead>(r: &mut R) -> io::Result<String> {
+    let mut line = String::new();
+    if r.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed",
+        ));
+    }
+    Ok(line.trim_end().to_string())
+}
+
+// One worker's connection: every job goes out as a line of JSON and comes back
+// as `ok` and the framebuffer of its block, or as `error: <message>`.
+struct Connection {
+    address: String,
+    reader: BufReader<Stream>,
+    writer: BufWriter<Stream>,
+}
+
+impl Connection {
+    fn open(address: &str) -> Result<Self, String> {
+        let error = |e: io::Error| format!("Cannot connect to worker '{}': {}", address, e);
+        let stream = Stream::connect(address).map_err(error)?;
+        let mut writer = BufWriter::new(stream.try_clone().map_err(error)?);
+        writeln!(writer, "{}", PROTOCOL)
+            .and_then(|_| writer.flush())
+            .map_err(error)?;
+        Ok(Self {
+            address: String::from(address),
+            reader: BufReader::new(stream),
+            writer,
+        })
+    }
+    fn render(&mut self, request: &Request) -> Result<FrameBuffer, String> {
+        let address = self.address.clone();
+        let error = |e: io::Error| format!("Worker '{}' failed: {}", address, e);
+        let json = serde_json::to_string(request).map_err(|e| e.to_string())?;
+        writeln!(self.writer, "{}", json)
+            .and_then(|_| self.writer.flush())
+            .map_err(error)?;
+        let status = read_line(&mut self.reader).map_err(error)?;
+        if let Some(message) = status.strip_prefix("error: ") {
+            return Err(format!("Worker '{}': {}", address, message));
+        }
+        let job = request.job;
+        FrameBuffer::load(&mut self.reader, job.width, job.height).map_err(error)
+    }
+}
+
+// the blocks of the rendered part of the image and, when there are too few
+// of them to keep every worker busy, ranges of their samples
+fn jobs(camera: &Camera, workers: usize) -> Vec<Job> {
+    let blocks = tile::split_window(
+        camera.image_width,
+        camera.image_height,
+        JOB_SIZE,
+        camera.window,
+    );
+    let wanted = workers as u32 * JOBS_PER_WORKER;
+    // adaptive sampling decides on all of a pixel's samples at once
+    let ranges = if camera.noise_threshold > 0.0 || blocks.len() as u32 >= wanted {
+        1
+    } else {
+        let blocks = blocks.len().max(1) as u32;
+        ((wanted + blocks - 1) / blocks).min(camera.samples_per_pixel)
+    };
+    let spp = camera.samples_per_pixel;
+    let mut jobs = vec![];
+    for block in blocks {
+        for r in 0..ranges {
+            let first_sample = spp * r / ranges;
+            jobs.push(Job {
+                x: block.x,
+                y: block.y,
+                width: block.width,
+                height: block.height,
+                first_sample,
+                samples: spp * (r + 1) / ranges - first_sample,
+            });
+        }
+    }
+    jobs
+}
+
+// The jobs still to hand out, and how many are out with workers. A job
+// that fails comes back, so workers wait for those out before they stop.
+struct Queue {
+    waiting: VecDeque<usize>,
+    out: usize,
+}
+
+// Renders `scene` on the workers at `addresses` and merges what they send
+// back. `name` is the built-in scene or the scene file, whose text `source`
+// holds. Jobs are handed out as workers finish their last one, a job whose
+// worker fails goes to the others, and the results are merged in a fixed
+// order so the image does not depend on who rendered what.
+pub fn render(
+    scene: &Scene,
+    name: &str,
+    source: Option<String>,
+    addresses: &[String],
+) -> Result<Checkpoint, String> {
+    let start = Instant::now();
+    let mut params = RenderParams::new(name, scene);
+    let features = scene.denoise.is_some() || !scene.aovs.is_empty();
+    let camera = Camera::new(scene.image_settings.clone(), scene.camera_settings.clone());
+    if camera.window.pixels() == 0 {
+        return Err(String::from("The crop window lies outside the image"));
+    }
+    let jobs = jobs(&camera, addresses.len());
+    let queue = Mutex::new(Queue {
+        waiting: (0..jobs.len()).collect(),
+        out: 0,
+    });
+    let returned = Condvar::new();
+    let results: Mutex<Vec<Option<FrameBuffer>>> = Mutex::new(vec![None; jobs.len()]);
+    let errors = Mutex::new(vec![]);
+    let progress = if option_env!("CI").unwrap_or_default() == "true" {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(jobs.len() as u64)
+    };
+
+    thread::scope(|scope| {
+        for address in addresses {
+            let (jobs, queue, returned) = (&jobs, &queue, &returned);
+            let (results, errors) = (&results, &errors);
+            let (params, source, progress) = (&params, &source, &progress);
+            scope.spawn(move || {
+                let mut connection = match Connection::open(address) {
+                    Ok(connection) => connection,
+                    Err(message) => return errors.lock().unwrap().push(message),
+                };
+                loop {
+                    let index = {
+                        let mut queue = queue.lock().unwrap();
+                        loop {
+                            if let Some(index) = queue.waiting.pop_front() {
+                                queue.out += 1;
+                                break index;
+                            }
+                            if queue.out == 0 {
+                                return;
+                            }
+                            queue = returned.wait(queue).unwrap();
+                        }
+                    };
+                    let request = Request {
+                        params: params.clone(),
+                        source: source.clone(),
+                        features,
+                        job: jobs[index],
+                    };
+                    match connection.render(&request) {
+                        Ok(framebuffer) => {
+                            results.lock().unwrap()[index] = Some(framebuffer);
+                            progress.inc(1);
+                            queue.lock().unwrap().out -= 1;
+                            returned.notify_all();
+                        }
+                        Err(message) => {
+                            errors.lock().unwrap().push(message);
+                            let mut queue = queue.lock().unwrap();
+                            queue.waiting.push_back(index);
+                            queue.out -= 1;
+                            returned.notify_all();
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+    });
+    progress.finish();
+
+    let mut framebuffer = if features {
+        FrameBuffer::with_features(camera.image_width, camera.image_height)
+    } else {
+        FrameBuffer::new(camera.image_width, camera.image_height)
+    };
+    for (job, result) in jobs.iter().zip(results.into_inner().unwrap()) {
+        match result {
+            Some(block) => framebuffer.merge_at(&block, job.x, job.y),
+            None => {
+                let errors = errors.into_inner().unwrap();
+                return Err(errors
+                    .last()
+                    .cloned()
+                    .unwrap_or_else(|| String::from("No worker rendered the frame")));
+            }
+        }
+    }
+    params.passes = 1;
+    params.elapsed = start.elapsed().as_secs_f64();
+    Ok(Checkpoint {
+        params,
+        framebuffer,
+    })
+}
+
+// Serves coordinators at `address` until the process is stopped, every
+// connection on a thread of its own. `load` makes a scene from its name or
+// the text of its scene file.
+pub fn serve<L>(address: &str, load: L) -> Result<(), String>
+where
+    L: Fn(&str, Option<&str>) -> Result<Scene, String> + Sync,
+{
+    let error = |e: io::Error| format!("Cannot listen on '{}': {}", address, e);
+    let load = &load;
+    thread::scope(|scope| {
+        match address.strip_prefix("unix:") {
+            #[cfg(unix)]
+            Some(path) => {
+                // a socket left behind by an earlier worker
+                let _ = std::fs::remove_file(path);
+                let listener = UnixListener::bind(path).map_err(error)?;
+                for stream in listener.incoming() {
+                    let stream = Stream::Unix(stream.map_err(error)?);
+                    scope.spawn(move || report(work(stream, load)));
+                }
+            }
+            #[cfg(not(unix))]
+            Some(_) => return Err(String::from("Unix sockets are not supported here")),
+            None => {
+                let listener = TcpListener::bind(address).map_err(error)?;
+                for stream in listener.incoming() {
+                    let stream = stream.and_then(Stream::tcp).map_err(error)?;
+                    scope.spawn(move || report(work(stream, load)));
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn report(result: Result<(), String>) {
+    if let Err(message) = result {
+        eprintln!("{}", message);
+    }
+}
+
+// A scene set up for the jobs of one frame
+struct Loaded {
+    key: String,
+    camera: Camera,
+    world: HittableList,
+    lights: Option<Arc<dyn Hittable>>,
+}
+
+// Renders the jobs of one coordinator. The scene is only loaded again when
+// the request asks for another one, so it is built once per frame.
+fn work<L>(stream: Stream, load: &L) -> Result<(), String>
+where
+    L: Fn(&str, Option<&str>) -> Result<Scene, String>,
+{
+    let error = |e: io::Error| format!("Connection lost: {}", e);
+    let mut writer = BufWriter::new(stream.try_clone().map_err(error)?);
+    let mut reader = BufReader::new(stream);
+    if read_line(&mut reader).map_err(error)? != PROTOCOL {
+        return Err(String::from("Not a raytracer coordinator"));
+    }
+    let mut loaded: Option<Loaded> = None;
+    loop {
+        let line = match read_line(&mut reader) {
+            Ok(line) => line,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(error(e)),
+        };
+        let result = serde_json::from_str::<Request>(&line)
+            .map_err(|e| format!("Invalid request: {}", e))
+            .and_then(|request| {
+                let key =
+                    serde_json::to_string(&(&request.params, &request.source, request.features))
+                        .map_err(|e| e.to_string())?;
+                if loaded.as_ref().map_or(true, |loaded| loaded.key != key) {
+                    let mut scene = load(&request.params.scene, request.source.as_deref())?;
+                    request.params.apply(&mut scene)?;
+                    let mut camera = Camera::new(scene.image_settings, scene.camera_settings);
+                    camera.features = request.features;
+                    // every job renders into a buffer of its own size
+                    camera.framebuffer = FrameBuffer::new(0, 0);
+                    loaded = Some(Loaded {
+                        key,
+                        camera,
+                        world: scene.world,
+                        lights: scene.lights,
+                    });
+                }
+                Ok(render_job(loaded.as_mut().unwrap(), request.job))
+            });
+        match result {
+            Ok(framebuffer) => {
+                writeln!(writer, "ok").map_err(error)?;
+                framebuffer.save(&mut writer).map_err(error)?;
+            }
+            Err(message) => writeln!(writer, "error: {}", message).map_err(error)?,
+        }
+        writer.flush().map_err(error)?;
+    }
+}
+
+fn render_job(loaded: &mut Loaded, job: Job) -> FrameBuffer {
+    let camera = &mut loaded.camera;
+    camera.window = Tile {
+        x: job.x,
+        y: job.y,
+        width: job.width,
+        height: job.height,
+    };
+    camera.first_sample = job.first_sample;
+    camera.framebuffer = FrameBuffer::new(job.width, job.height);
+    camera.render_pass(&loaded.world, &loaded.lights, job.samples);
+    std::mem::replace(&mut camera.framebuffer, FrameBuffer::new(0, 0))
+}
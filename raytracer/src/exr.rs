@@ -0,0 +1,108 @@
+use std::io::{self, Write};
+
+// Minimal OpenEXR writer: single part, scan lines, no compression and 32-bit
+// float channels only. That is enough for renders and any reader accepts it.
+pub struct ExrChannel {
+    pub name: String,
+    // width * height values, row by row from the top
+    pub data: Vec<f32>,
+}
+
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+fn attribute<W: Write>(w: &mut W, name: &str, kind: &str, value: &[u8]) -> io::Result<()> {
+    w.write_all(name.as_bytes())?;
+    w.write_all(&[0])?;
+    w.write_all(kind.as_bytes())?;
+    w.write_all(&[0])?;
+    w.write_all(&(value.len() as i32).to_le_bytes())?;
+    w.write_all(value)
+}
+
+fn box2i(width: u32, height: u32) -> Vec<u8> {
+    let mut value = vec![];
+    for v in [0, 0, width as i32 - 1, height as i32 - 1].iter() {
+        value.extend_from_slice(&v.to_le_bytes());
+    }
+    value
+}
+
+// `attributes` are extra string attributes stored in the header, e.g. render statistics
+pub fn write_exr<W: Write>(
+    w: &mut W,
+    width: u32,
+    height: u32,
+    channels: &mut [ExrChannel],
+    attributes: &[(String, String)],
+) -> io::Result<()> {
+    // readers expect the channel list sorted by name
+    channels.sort_by(|a, b| a.name.cmp(&b.name));
+    for channel in channels.iter() {
+        assert_eq!(channel.data.len(), (width * height) as usize);
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+    header.extend_from_slice(&2u32.to_le_bytes());
+
+    let mut chlist = vec![];
+    for channel in channels.iter() {
+        chlist.extend_from_slice(channel.name.as_bytes());
+        chlist.push(0);
+        chlist.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+        chlist.extend_from_slice(&1i32.to_le_bytes());
+    }
+    chlist.push(0);
+    attribute(&mut header, "channels", "chlist", &chlist)?;
+    attribute(&mut header, "compression", "compression", &[0])?;
+    attribute(&mut header, "dataWindow", "box2i", &box2i(width, height))?;
+    attribute(&mut header, "displayWindow", "box2i", &box2i(width, height))?;
+    attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    let mut center = vec![];
+    center.extend_from_slice(&0f32.to_le_bytes());
+    center.extend_from_slice(&0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &center)?;
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    for (name, value) in attributes {
+        attribute(&mut header, name, "string", value.as_bytes())?;
+    }
+    header.push(0);
+
+    // one scan line per chunk: y, byte count, then every channel's row in turn
+    let line_size = 4 * width as usize * channels.len();
+    let chunk_size = 8 + line_size;
+    let table_end = header.len() + 8 * height as usize;
+    for y in 0..height as usize {
+        let offset = (table_end + y * chunk_size) as u64;
+        header.extend_from_slice(&offset.to_le_bytes());
+    }
+    w.write_all(&header)?;
+
+    let mut line = Vec::with_capacity(chunk_size);
+    for y in 0..height as usize {
+        line.clear();
+        line.extend_from_slice(&(y as i32).to_le_bytes());
+        line.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in channels.iter() {
+            let row = &channel.data[y * width as usize..(y + 1) * width as usize];
+            for v in row {
+                line.extend_from_slice(&v.to_le_bytes());
+            }
+        }
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
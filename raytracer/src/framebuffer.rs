@@ -0,0 +1,367 @@
+use crate::color::Color;
+use crate::tonemap::ToneMapping;
+use crate::vec3::{Point3, Vec3};
+use image::{ImageBuffer, Rgb, RgbImage};
+use std::io::{self, Read, Write};
+
+// What a camera sample saw at its first hit, summed per pixel like the
+// radiance. Rays that miss everything leave the normal, uv and position at
+// zero, the depth at infinity and the object id at 0.
+#[derive(Copy, Clone, Debug)]
+pub struct Features {
+    pub albedo: Color,
+    pub normal: Vec3,
+    // distance from the camera
+    pub depth: f64,
+    pub uv: [f64; 2],
+    pub position: Point3,
+    pub object_id: u32,
+}
+
+impl Features {
+    pub fn miss(background: Color) -> Self {
+        Self {
+            albedo: background,
+            normal: Vec3::new(0.0, 0.0, 0.0),
+            depth: f64::INFINITY,
+            uv: [0.0, 0.0],
+            position: Point3::new(0.0, 0.0, 0.0),
+            object_id: 0,
+        }
+    }
+    fn hit(&self) -> bool {
+        self.depth.is_finite()
+    }
+    // The albedo and normal are summed over all samples, the rest only over
+    // the samples that hit something. Ids cannot be averaged, a pixel keeps
+    // the first one it sees.
+    fn accumulate(&mut self, other: &Features) {
+        self.albedo += other.albedo;
+        self.normal += other.normal;
+        self.depth += other.depth;
+        self.uv[0] += other.uv[0];
+        self.uv[1] += other.uv[1];
+        self.position += other.position;
+        if self.object_id == 0 {
+            self.object_id = other.object_id;
+        }
+    }
+}
+
+// Linear radiance accumulated per pixel. Every pixel keeps the sum of its samples
+// and how many there were, so more passes can be added later and the mean
+// is only taken when the image is written out. The sum of squared luminances
+// gives the variance of the samples, which drives adaptive sampling.
+// Buffers made `with_features` also sum the first hit features, for the
+// denoiser and the AOV outputs.
+#[derive(Clone)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    sum: Vec<Color>,
+    sum_sq: Vec<f64>,
+    samples: Vec<u32>,
+    features: Vec<Features>,
+    // samples that hit something
+    hits: Vec<u32>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        Self {
+            width,
+            height,
+            sum: vec![Color::black(); len],
+            sum_sq: vec![0.0; len],
+            samples: vec![0; len],
+            features: vec![],
+            hits: vec![],
+        }
+    }
+    pub fn with_features(width: u32, height: u32) -> Self {
+        let len = (width * height) as usize;
+        let mut zero = Features::miss(Color::black());
+        zero.depth = 0.0;
+        Self {
+            features: vec![zero; len],
+            hits: vec![0; len],
+            ..Self::new(width, height)
+        }
+    }
+    pub fn has_features(&self) -> bool {
+        !self.hits.is_empty()
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    fn index(&self, i: u32, j: u32) -> usize {
+        (j * self.width + i) as usize
+    }
+    pub fn add_sample(&mut self, i: u32, j: u32, color: Color) {
+        let index = self.index(i, j);
+        self.sum[index] += color;
+        self.sum_sq[index] += color.luminance() * color.luminance();
+        self.samples[index] += 1;
+    }
+    // goes with one `add_sample`, ignored unless the buffer has features
+    pub fn add_features(&mut self, i: u32, j: u32, features: &Features) {
+        if !self.has_features() {
+            return;
+        }
+        let index = self.index(i, j);
+        if features.hit() {
+            self.features[index].accumulate(features);
+            self.hits[index] += 1;
+        } else {
+            self.features[index].albedo += features.albedo;
+        }
+    }
+    pub fn merge(&mut self, other: &FrameBuffer) {
+        assert!(self.width == other.width && self.height == other.height);
+        for index in 0..self.sum.len() {
+            self.sum[index] += other.sum[index];
+            self.sum_sq[index] += other.sum_sq[index];
+            self.samples[index] += other.samples[index];
+        }
+        if self.has_features() && other.has_features() {
+            for index in 0..self.sum.len() {
+                self.merge_features(index, other, index);
+            }
+        }
+    }
+    // a copy of the `width` x `height` pixels starting at (x, y)
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> FrameBuffer {
+        assert!(x + width <= self.width && y + height <= self.height);
+        let mut crop = if self.has_features() {
+            FrameBuffer::with_features(width, height)
+        } else {
+            FrameBuffer::new(width, height)
+        };
+        for j in 0..height {
+            for i in 0..width {
+                crop.copy_pixel(crop.index(i, j), self, self.index(x + i, y + j));
+            }
+        }
+        crop
+    }
+    // overwrites the pixels under a smaller buffer whose top left pixel lands on (x, y)
+    pub fn paste(&mut self, other: &FrameBuffer, x: u32, y: u32) {
+        assert!(x + other.width <= self.width && y + other.height <= self.height);
+        for j in 0..other.height {
+            for i in 0..other.width {
+                self.copy_pixel(self.index(x + i, y + j), other, other.index(i, j));
+            }
+        }
+    }
+    fn copy_pixel(&mut self, to: usize, other: &FrameBuffer, from: usize) {
+        self.sum[to] = other.sum[from];
+        self.sum_sq[to] = other.sum_sq[from];
+        self.samples[to] = other.samples[from];
+        if self.has_features() && other.has_features() {
+            self.features[to] = other.features[from];
+            self.hits[to] = other.hits[from];
+        }
+    }
+    // adds a smaller buffer whose top left pixel lands on (x, y)
+    pub fn merge_at(&mut self, other: &FrameBuffer, x: u32, y: u32) {
+        assert!(x + other.width <= self.width && y + other.height <= self.height);
+        for j in 0..other.height {
+            for i in 0..other.width {
+                let from = other.index(i, j);
+                let to = self.index(x + i, y + j);
+                self.sum[to] += other.sum[from];
+                self.sum_sq[to] += other.sum_sq[from];
+                self.samples[to] += other.samples[from];
+                if self.has_features() && other.has_features() {
+                    self.merge_features(to, other, from);
+                }
+            }
+        }
+    }
+    fn merge_features(&mut self, to: usize, other: &FrameBuffer, from: usize) {
+        self.features[to].accumulate(&other.features[from]);
+        self.hits[to] += other.hits[from];
+    }
+    // The raw sums in little endian, so a render can be picked up where it
+    // stopped
+    pub fn save<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.width.to_le_bytes())?;
+        w.write_all(&self.height.to_le_bytes())?;
+        w.write_all(&[self.has_features() as u8])?;
+        let f64s = |w: &mut W, values: &[f64]| -> io::Result<()> {
+            for v in values {
+                w.write_all(&v.to_le_bytes())?;
+            }
+            Ok(())
+        };
+        for index in 0..self.sum.len() {
+            let sum = self.sum[index];
+            f64s(w, &[sum.x, sum.y, sum.z, self.sum_sq[index]])?;
+            w.write_all(&self.samples[index].to_le_bytes())?;
+            if self.has_features() {
+                let f = &self.features[index];
+                f64s(w, &[f.albedo.x, f.albedo.y, f.albedo.z])?;
+                f64s(w, &[f.normal.x, f.normal.y, f.normal.z, f.depth])?;
+                f64s(
+                    w,
+                    &[f.uv[0], f.uv[1], f.position.x, f.position.y, f.position.z],
+                )?;
+                w.write_all(&f.object_id.to_le_bytes())?;
+                w.write_all(&self.hits[index].to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    // fails unless the buffer is `width` x `height`, so a broken file or
+    // connection cannot make it allocate any amount
+    pub fn load<R: Read>(r: &mut R, width: u32, height: u32) -> io::Result<FrameBuffer> {
+        let u32s = |r: &mut R| -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            r.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        };
+        let size = (u32s(r)?, u32s(r)?);
+        if size != (width, height) || width.checked_mul(height).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the framebuffer is {}x{}, not {}x{}",
+                    size.0, size.1, width, height
+                ),
+            ));
+        }
+        let mut flag = [0];
+        r.read_exact(&mut flag)?;
+        let mut framebuffer = if flag[0] == 1 {
+            FrameBuffer::with_features(width, height)
+        } else {
+            FrameBuffer::new(width, height)
+        };
+        let f64s = |r: &mut R, n: usize| -> io::Result<Vec<f64>> {
+            let mut values = Vec::with_capacity(n);
+            let mut bytes = [0; 8];
+            for _ in 0..n {
+                r.read_exact(&mut bytes)?;
+                values.push(f64::from_le_bytes(bytes));
+            }
+            Ok(values)
+        };
+        for index in 0..framebuffer.sum.len() {
+            let v = f64s(r, 4)?;
+            framebuffer.sum[index] = Color::new(v[0], v[1], v[2]);
+            framebuffer.sum_sq[index] = v[3];
+            framebuffer.samples[index] = u32s(r)?;
+            if framebuffer.has_features() {
+                let v = f64s(r, 12)?;
+                framebuffer.features[index] = Features {
+                    albedo: Color::new(v[0], v[1], v[2]),
+                    normal: Vec3::new(v[3], v[4], v[5]),
+                    depth: v[6],
+                    uv: [v[7], v[8]],
+                    position: Point3::new(v[9], v[10], v[11]),
+                    object_id: u32s(r)?,
+                };
+                framebuffer.hits[index] = u32s(r)?;
+            }
+        }
+        Ok(framebuffer)
+    }
+    pub fn sum(&self, i: u32, j: u32) -> Color {
+        self.sum[self.index(i, j)]
+    }
+    pub fn total_samples(&self) -> u64 {
+        self.samples.iter().map(|&n| n as u64).sum()
+    }
+    pub fn samples(&self, i: u32, j: u32) -> u32 {
+        self.samples[self.index(i, j)]
+    }
+    // mean radiance of the pixel, black if it has not been sampled yet
+    pub fn pixel(&self, i: u32, j: u32) -> Color {
+        let index = self.index(i, j);
+        if self.samples[index] == 0 {
+            Color::black()
+        } else {
+            self.sum[index] / self.samples[index] as f64
+        }
+    }
+    // mean first hit features of the pixel
+    pub fn features(&self, i: u32, j: u32) -> Features {
+        let index = self.index(i, j);
+        let sum = &self.features[index];
+        let samples = self.samples[index].max(1) as f64;
+        let mut features = Features::miss(sum.albedo / samples);
+        features.normal = sum.normal / samples;
+        features.object_id = sum.object_id;
+        if self.hits[index] > 0 {
+            let hits = self.hits[index] as f64;
+            features.depth = sum.depth / hits;
+            features.uv = [sum.uv[0] / hits, sum.uv[1] / hits];
+            features.position = sum.position / hits;
+        }
+        features
+    }
+    // sample variance of the luminance
+    pub fn variance(&self, i: u32, j: u32) -> f64 {
+        let index = self.index(i, j);
+        let n = self.samples[index] as f64;
+        if n < 2.0 {
+            return f64::INFINITY;
+        }
+        let mean = self.sum[index].luminance() / n;
+        ((self.sum_sq[index] - n * mean * mean) / (n - 1.0)).max(0.0)
+    }
+    // Standard error of the mean luminance relative to the mean. The mean is
+    // floored so nearly black pixels do not ask for endless samples.
+    pub fn relative_error(&self, i: u32, j: u32) -> f64 {
+        let n = self.samples(i, j) as f64;
+        let mean = self.pixel(i, j).luminance();
+        (self.variance(i, j) / n).sqrt() / mean.max(0.01)
+    }
+    // samples per pixel in false color, from black (fewest) to pale yellow (most)
+    pub fn sample_heatmap(&self) -> RgbImage {
+        const STOPS: [[f64; 3]; 5] = [
+            [0.0, 0.0, 4.0],
+            [87.0, 16.0, 110.0],
+            [188.0, 55.0, 84.0],
+            [249.0, 142.0, 9.0],
+            [252.0, 255.0, 164.0],
+        ];
+        let max = self.samples.iter().copied().max().unwrap_or(0).max(1) as f64;
+        RgbImage::from_fn(self.width, self.height, |i, j| {
+            let t = self.samples(i, j) as f64 / max * (STOPS.len() - 1) as f64;
+            let k = (t as usize).min(STOPS.len() - 2);
+            let f = t - k as f64;
+            let mut rgb = [0; 3];
+            for (c, value) in rgb.iter_mut().enumerate() {
+                *value = (STOPS[k][c] + (STOPS[k + 1][c] - STOPS[k][c]) * f).round() as u8;
+            }
+            Rgb(rgb)
+        })
+    }
+    pub fn to_rgb_image(&self, tone_mapping: &ToneMapping) -> RgbImage {
+        let mut img = RgbImage::new(self.width, self.height);
+        for (i, j, pixel) in img.enumerate_pixels_mut() {
+            *pixel = self.pixel(i, j).write_color(tone_mapping);
+        }
+        img
+    }
+    pub fn to_rgb16_image(&self, tone_mapping: &ToneMapping) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        ImageBuffer::from_fn(self.width, self.height, |i, j| {
+            self.pixel(i, j).write_color16(tone_mapping)
+        })
+    }
+    // linear float pixels, row by row from the top
+    pub fn to_linear(&self) -> Vec<Rgb<f32>> {
+        let mut pixels = Vec::with_capacity(self.sum.len());
+        for j in 0..self.height {
+            for i in 0..self.width {
+                pixels.push(self.pixel(i, j).write_linear());
+            }
+        }
+        pixels
+    }
+}
@@ -2,6 +2,7 @@ use crate::aabb::Aabb;
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::vec3;
 use crate::vec3::{Point3, Vec3};
 use std::sync::Arc;
@@ -15,6 +16,8 @@ pub struct HitRecord {
     pub front_face: bool,
     pub u: f64,
     pub v: f64,
+    // set by `ObjectId`, 0 otherwise
+    pub object_id: u32,
 }
 
 impl HitRecord {
@@ -40,17 +43,49 @@ impl HitRecord {
             front_face,
             u,
             v,
+            object_id: 0,
         }
     }
 }
 
 pub trait Hittable: Send + Sync {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord>; // Some(hit_record) None
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord>; // Some(hit_record) None
     fn bounding_box(&self) -> Aabb;
+    // has no random numbers to draw from, so shapes that can be lights keep
+    // their intersection test apart from `hit` and call it here directly
     fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
         0.0
     }
-    fn random(&self, _origin: &Point3) -> Vec3 {
+    fn random(&self, _origin: &Point3, _rng: &mut SampleRng) -> Vec3 {
         Vec3::new(1.0, 0.0, 0.0)
     }
 }
+
+// Tags every hit on `object` with `id`, so the renders can tell objects apart
+pub struct ObjectId {
+    object: Arc<dyn Hittable>,
+    id: u32,
+}
+
+impl ObjectId {
+    pub fn new(object: Arc<dyn Hittable>, id: u32) -> Self {
+        Self { object, id }
+    }
+}
+
+impl Hittable for ObjectId {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
+        let mut rec = self.object.hit(r, ray_t, rng)?;
+        rec.object_id = self.id;
+        Some(rec)
+    }
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        self.object.pdf_value(origin, direction)
+    }
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        self.object.random(origin, rng)
+    }
+}
@@ -1,9 +1,9 @@
 use crate::aabb::Aabb;
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{HitRecord, Hittable, ObjectId};
 use crate::interval::Interval;
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::vec3::{Point3, Vec3};
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -31,14 +31,29 @@ impl HittableList {
         hl.add(object);
         hl
     }
+    // every object tagged with its position in the list, counting from 1,
+    // so that 0 is left for the background
+    pub fn with_object_ids(&self) -> Self {
+        let mut hl = Self::new();
+        for (k, object) in self.objects.iter().enumerate() {
+            hl.add(Arc::new(ObjectId::new(object.clone(), k as u32 + 1)));
+        }
+        hl
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
         let mut rec: Option<HitRecord> = None;
         let mut closest_so_far: f64 = ray_t.max;
         for object in &self.objects {
-            if let Some(tmp_rec) = object.hit(r, Interval::new(ray_t.min, closest_so_far)) {
+            if let Some(tmp_rec) = object.hit(r, Interval::new(ray_t.min, closest_so_far), rng) {
                 closest_so_far = tmp_rec.t;
                 rec = Some(tmp_rec);
             }
@@ -59,8 +74,9 @@ impl Hittable for HittableList {
         sum
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
         let size = self.objects.len();
-        self.objects[thread_rng().gen_range(0..size)].random(origin)
+        let k = ((rng.get_1d() * size as f64) as usize).min(size - 1);
+        self.objects[k].random(origin, rng)
     }
 }
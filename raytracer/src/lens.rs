@@ -0,0 +1,44 @@
+// Newton steps to invert the distortion, plenty for lenses that can be shot
+const UNDISTORT_STEPS: u32 = 8;
+
+// Flaws of a real lens, to match plates shot through one
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LensEffects {
+    // Brown-Conrady radial coefficients k1, k2 and k3 on image coordinates
+    // divided by the focus distance, as lens calibrations give them. A
+    // negative k1 bows straight lines out (barrel), a positive one in
+    // (pincushion).
+    pub distortion: [f64; 3],
+    // darkens the image towards its edges by cos⁴ of the angle off the axis
+    pub vignetting: bool,
+    // how much larger the red image is than the green, and the blue smaller
+    pub chromatic_aberration: f64,
+}
+
+impl LensEffects {
+    // how much larger the image of `channel` is than the green one
+    pub fn magnification(&self, channel: usize) -> f64 {
+        [
+            1.0 + self.chromatic_aberration,
+            1.0,
+            1.0 - self.chromatic_aberration,
+        ][channel]
+    }
+    // The radius in the scene that the lens images at radius `r`, both over
+    // the focus distance. The lens maps r to r (1 + k1 r² + k2 r⁴ + k3 r⁶),
+    // this solves that back by Newton's method.
+    pub fn undistort(&self, r: f64) -> f64 {
+        let [k1, k2, k3] = self.distortion;
+        let mut x = r;
+        for _ in 0..UNDISTORT_STEPS {
+            let x2 = x * x;
+            let f = x * (1.0 + x2 * (k1 + x2 * (k2 + x2 * k3))) - r;
+            let df = 1.0 + x2 * (3.0 * k1 + x2 * (5.0 * k2 + x2 * 7.0 * k3));
+            if df <= 0.0 {
+                break;
+            }
+            x -= f / df;
+        }
+        x.max(0.0)
+    }
+}
@@ -0,0 +1,77 @@
+pub mod aabb;
+pub mod adaptive;
+pub mod animation;
+pub mod aov;
+pub mod aperture;
+pub mod bvh;
+pub mod camera;
+pub mod color;
+pub mod denoise;
+pub mod distributed;
+pub mod edge;
+pub mod exr;
+pub mod framebuffer;
+pub mod hittable;
+pub mod hittable_list;
+pub mod interval;
+pub mod lens;
+pub mod material;
+pub mod medium;
+pub mod metadata;
+pub mod motion;
+pub mod obj;
+pub mod onb;
+pub mod output;
+pub mod pdf;
+pub mod perlin;
+pub mod physical;
+pub mod progressive;
+pub mod projection;
+pub mod quad;
+pub mod ray;
+pub mod rng;
+pub mod rtw_stb_image;
+pub mod sampler;
+pub mod scene;
+pub mod scene_file;
+pub mod shutter;
+pub mod sphere;
+pub mod stereo;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod translate;
+pub mod triangle;
+pub mod vec3;
+
+pub use crate::camera::{Camera, CameraSettings, ImageSettings};
+pub use crate::framebuffer::FrameBuffer;
+pub use crate::hittable::Hittable;
+pub use crate::material::Material;
+pub use crate::pdf::Pdf;
+pub use crate::scene::{Scene, SceneBuilder};
+pub use crate::texture::Texture;
+pub use crate::tonemap::{ToneMapper, ToneMapping};
+
+use image::RgbImage;
+
+// linear radiance, for HDR output or further passes. Scenes that ask for
+// denoising or AOVs get the first hit features as well, the noisy samples are
+// returned.
+pub fn render_framebuffer(scene: Scene) -> FrameBuffer {
+    let mut camera = Camera::new(scene.image_settings, scene.camera_settings);
+    camera.features = scene.denoise.is_some() || !scene.aovs.is_empty();
+    camera.render(scene.world, scene.lights);
+    camera.framebuffer
+}
+
+pub fn render(scene: Scene) -> RgbImage {
+    let tone_mapping = scene.tone_mapping.clone();
+    let denoise = scene.denoise;
+    let framebuffer = render_framebuffer(scene);
+    match denoise {
+        Some(settings) => denoise::denoise(&framebuffer, &settings),
+        None => framebuffer,
+    }
+    .to_rgb_image(&tone_mapping)
+}
@@ -2,9 +2,9 @@ use crate::color::Color;
 use crate::hittable::HitRecord;
 use crate::pdf::{CosinePDF, Pdf, SpherePDF};
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::texture::{SolidColor, Texture};
 use crate::vec3::{dot, random_unit_vector, reflect, refract, unit_vector, Point3};
-use rand::Rng;
 use std::sync::Arc;
 
 pub struct ScatterRecord {
@@ -18,7 +18,12 @@ pub trait Material: Send + Sync {
     fn emitted(&self, _r_in: &Ray, _rec: &HitRecord, _u: f64, _v: f64, _p: &Point3) -> Color {
         Color::black()
     }
-    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(
+        &self,
+        _r_in: &Ray,
+        _rec: &HitRecord,
+        _rng: &mut SampleRng,
+    ) -> Option<ScatterRecord> {
         None
     }
     fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> f64 {
@@ -43,7 +48,7 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut SampleRng) -> Option<ScatterRecord> {
         let attenuation = self.tex.value(rec.u, rec.v, &rec.p);
         let pdf_ptr = Arc::new(CosinePDF::new(&rec.normal));
         let skip_pdf = false;
@@ -78,9 +83,9 @@ impl Metal {
 }
 
 impl Material for Metal {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut SampleRng) -> Option<ScatterRecord> {
         let mut reflected = reflect(&r_in.direction(), &rec.normal);
-        reflected = unit_vector(&reflected) + random_unit_vector() * self.fuzz;
+        reflected = unit_vector(&reflected) + random_unit_vector(rng) * self.fuzz;
         let scattered = Ray::new(&rec.p, &reflected, r_in.time());
         let attenuation = self.albedo;
 
@@ -103,7 +108,7 @@ pub struct Dielectric {
 }
 
 impl Dielectric {
-    pub(crate) fn new(refraction_index: f64) -> Self {
+    pub fn new(refraction_index: f64) -> Self {
         Self { refraction_index }
     }
     pub fn reflectance(cos: f64, refraction_index: f64) -> f64 {
@@ -114,7 +119,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, rng: &mut SampleRng) -> Option<ScatterRecord> {
         let attenuation = Color::white();
         let ri = if rec.front_face {
             1.0 / self.refraction_index
@@ -126,13 +131,11 @@ impl Material for Dielectric {
         let cos_theta = f64::min(dot(&(-unit_direction), &rec.normal), 1.0);
         let sin_theta = f64::sqrt(1.0 - cos_theta * cos_theta);
 
-        let mut rng = rand::thread_rng();
-        let direction =
-            if ri * sin_theta > 1.0 || Self::reflectance(cos_theta, ri) > rng.gen_range(0.0..1.0) {
-                reflect(&unit_direction, &rec.normal)
-            } else {
-                refract(&unit_direction, &rec.normal, ri)
-            };
+        let direction = if ri * sin_theta > 1.0 || Self::reflectance(cos_theta, ri) > rng.get_1d() {
+            reflect(&unit_direction, &rec.normal)
+        } else {
+            refract(&unit_direction, &rec.normal, ri)
+        };
 
         let scattered = Ray::new(&rec.p, &direction, r_in.time());
 
@@ -156,7 +159,7 @@ impl DiffuseLight {
             tex: Arc::new(SolidColor::new(emit)),
         }
     }
-    pub fn _new_tex(tex: Arc<dyn Texture>) -> Self {
+    pub fn new_tex(tex: Arc<dyn Texture>) -> Self {
         Self { tex }
     }
 }
@@ -181,13 +184,13 @@ impl Isotropic {
             tex: Arc::new(SolidColor::new(albedo)),
         }
     }
-    pub fn _new_tex(tex: Arc<dyn Texture>) -> Self {
+    pub fn new_tex(tex: Arc<dyn Texture>) -> Self {
         Self { tex }
     }
 }
 
 impl Material for Isotropic {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<ScatterRecord> {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, _rng: &mut SampleRng) -> Option<ScatterRecord> {
         let attenuation = self.tex.value(rec.u, rec.v, &rec.p);
         let pdf_ptr = Arc::new(SpherePDF::_new());
         let skip_pdf = false;
@@ -1,5 +1,4 @@
 use crate::aabb::Aabb;
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
 use crate::color::Color;
@@ -7,6 +6,7 @@ use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::{Isotropic, Material};
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::texture::Texture;
 use crate::vec3::{Point3, Vec3};
 
@@ -24,20 +24,20 @@ impl ConstantMedium {
             phase_function: Arc::new(Isotropic::new(albedo)),
         }
     }
-    pub fn _new_tex(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+    pub fn new_tex(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
         Self {
             boundary,
             neg_inv_density: -1.0 / density,
-            phase_function: Arc::new(Isotropic::_new_tex(tex)),
+            phase_function: Arc::new(Isotropic::new_tex(tex)),
         }
     }
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
         let mut rec1;
         let mut rec2;
-        let rec = self.boundary.hit(r, Interval::universe());
+        let rec = self.boundary.hit(r, Interval::universe(), rng);
         match rec {
             None => {
                 return None;
@@ -48,7 +48,7 @@ impl Hittable for ConstantMedium {
         }
         let rec = self
             .boundary
-            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY));
+            .hit(r, Interval::new(rec1.t + 0.0001, f64::INFINITY), rng);
         match rec {
             None => {
                 return None;
@@ -73,8 +73,7 @@ impl Hittable for ConstantMedium {
 
         let ray_len = r.direction().length();
         let dis_in_boundary = (rec2.t - rec1.t) * ray_len;
-        let hit_dis =
-            self.neg_inv_density * f64::log(thread_rng().gen_range(0.0..1.0), std::f64::consts::E);
+        let hit_dis = self.neg_inv_density * f64::log(rng.get_1d(), std::f64::consts::E);
 
         if hit_dis > dis_in_boundary {
             return None;
@@ -89,6 +88,7 @@ impl Hittable for ConstantMedium {
             front_face: true,
             u: rec1.u,
             v: rec1.v, // todo
+            object_id: rec1.object_id,
         };
         Some(rec)
     }
@@ -101,7 +101,7 @@ impl Hittable for ConstantMedium {
         self.boundary.pdf_value(origin, direction)
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
-        self.boundary.random(origin)
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        self.boundary.random(origin, rng)
     }
 }
@@ -0,0 +1,89 @@
+use crate::output::OutputFormat;
+
+// Adds name and value pairs to an encoded image: tEXt chunks for png, a
+// comment segment for jpeg and header lines for ppm and hdr. exr takes them
+// as header attributes while it is written and pfm has no place for them.
+pub fn embed(bytes: &[u8], format: OutputFormat, metadata: &[(String, String)]) -> Vec<u8> {
+    if metadata.is_empty() {
+        return bytes.to_vec();
+    }
+    match format {
+        // right after the signature and the IHDR chunk
+        OutputFormat::Png | OutputFormat::Png16 => {
+            let chunks: Vec<u8> = metadata
+                .iter()
+                .flat_map(|(name, value)| {
+                    png_chunk(
+                        b"tEXt",
+                        &[name.as_bytes(), b"\0", value.as_bytes()].concat(),
+                    )
+                })
+                .collect();
+            insert(bytes, 33, &chunks)
+        }
+        // right after the start of image marker
+        OutputFormat::Jpeg(_) => {
+            let text = lines(metadata, "", ": ");
+            let length = (text.len() + 2).min(u16::MAX as usize);
+            let mut segment = vec![0xff, 0xfe];
+            segment.extend_from_slice(&(length as u16).to_be_bytes());
+            segment.extend_from_slice(&text.as_bytes()[..length - 2]);
+            insert(bytes, 2, &segment)
+        }
+        // after the first line, which holds the format's magic
+        OutputFormat::Ppm => insert(
+            bytes,
+            first_line(bytes),
+            lines(metadata, "# ", ": ").as_bytes(),
+        ),
+        OutputFormat::Hdr => insert(
+            bytes,
+            first_line(bytes),
+            lines(metadata, "", "=").as_bytes(),
+        ),
+        OutputFormat::Exr | OutputFormat::Pfm => bytes.to_vec(),
+    }
+}
+
+fn lines(metadata: &[(String, String)], prefix: &str, separator: &str) -> String {
+    metadata
+        .iter()
+        .map(|(name, value)| format!("{}{}{}{}\n", prefix, name, separator, value))
+        .collect()
+}
+
+fn first_line(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(0, |at| at + 1)
+}
+
+fn insert(bytes: &[u8], at: usize, extra: &[u8]) -> Vec<u8> {
+    [&bytes[..at], extra, &bytes[at..]].concat()
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    chunk
+}
+
+// the CRC of zlib and png, bit by bit since the chunks are short
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,212 @@
+use crate::aabb::Aabb;
+use crate::animation::{Interpolation, Track};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::rng::SampleRng;
+use crate::vec3::{dot, unit_vector, Point3, Vec3};
+use std::sync::Arc;
+
+// times the bounding box is checked at over the motion, besides the keys
+const BOUNDS_STEPS: u32 = 64;
+
+// The pose of an object at a moment in time. The shutter is open from 0 to 1
+// unless the camera says otherwise.
+#[derive(Copy, Clone, Debug)]
+pub struct MotionKey {
+    pub time: f64,
+    pub translate: Vec3,
+    // degrees about the x, y and z axes, applied in that order
+    pub rotate: Vec3,
+    pub scale: Vec3,
+}
+
+impl MotionKey {
+    // the object as it is
+    pub fn at(time: f64) -> Self {
+        Self {
+            time,
+            translate: Vec3::new(0.0, 0.0, 0.0),
+            rotate: Vec3::new(0.0, 0.0, 0.0),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+    fn values(&self) -> [f64; 9] {
+        let (t, r, s) = (self.translate, self.rotate, self.scale);
+        [t.x, t.y, t.z, r.x, r.y, r.z, s.x, s.y, s.z]
+    }
+}
+
+// Scales, then rotates, then translates
+struct Transform {
+    translate: Vec3,
+    // rows of the rotation matrix
+    rotation: [Vec3; 3],
+    scale: Vec3,
+}
+
+impl Transform {
+    fn new(v: [f64; 9]) -> Self {
+        let [a, b, c] = [v[3], v[4], v[5]].map(f64::to_radians);
+        let (sa, ca, sb, cb, sc, cc) = (a.sin(), a.cos(), b.sin(), b.cos(), c.sin(), c.cos());
+        // Rz * Ry * Rx
+        let rotation = [
+            Vec3::new(cc * cb, cc * sb * sa - sc * ca, cc * sb * ca + sc * sa),
+            Vec3::new(sc * cb, sc * sb * sa + cc * ca, sc * sb * ca - cc * sa),
+            Vec3::new(-sb, cb * sa, cb * ca),
+        ];
+        Self {
+            translate: Vec3::new(v[0], v[1], v[2]),
+            rotation,
+            scale: Vec3::new(v[6], v[7], v[8]),
+        }
+    }
+    fn rotate(&self, v: Vec3) -> Vec3 {
+        let r = &self.rotation;
+        Vec3::new(dot(&r[0], &v), dot(&r[1], &v), dot(&r[2], &v))
+    }
+    fn unrotate(&self, v: Vec3) -> Vec3 {
+        let r = &self.rotation;
+        r[0] * v.x + r[1] * v.y + r[2] * v.z
+    }
+    fn vector_to_world(&self, v: Vec3) -> Vec3 {
+        self.rotate(v * self.scale)
+    }
+    fn point_to_world(&self, p: Point3) -> Point3 {
+        self.vector_to_world(p) + self.translate
+    }
+    fn vector_to_object(&self, v: Vec3) -> Vec3 {
+        let v = self.unrotate(v);
+        Vec3::new(v.x / self.scale.x, v.y / self.scale.y, v.z / self.scale.z)
+    }
+    fn point_to_object(&self, p: Point3) -> Point3 {
+        self.vector_to_object(p - self.translate)
+    }
+    // normals take the inverse transpose, which undoes the scale
+    fn normal_to_world(&self, n: Vec3) -> Vec3 {
+        let s = self.scale;
+        unit_vector(&self.rotate(Vec3::new(n.x / s.x, n.y / s.y, n.z / s.z)))
+    }
+}
+
+// Moves, turns and scales any object over time by keyframes, so it is
+// blurred along its motion. Rays are taken into the object's own space at
+// their time, the hits brought back out.
+pub struct Motion {
+    object: Arc<dyn Hittable>,
+    track: Track<9>,
+    bbox: Aabb,
+}
+
+impl Motion {
+    pub fn new(
+        object: Arc<dyn Hittable>,
+        keys: &[MotionKey],
+        interpolation: Interpolation,
+    ) -> Result<Self, String> {
+        if let Some(key) = keys
+            .iter()
+            .find(|key| key.scale.x * key.scale.y * key.scale.z == 0.0)
+        {
+            return Err(format!("Motion key at {} scales to nothing", key.time));
+        }
+        let track = Track::new(
+            keys.iter().map(|key| (key.time, key.values())).collect(),
+            interpolation,
+        )?;
+        let bbox = bounds(object.bounding_box(), &track);
+        Ok(Self {
+            object,
+            track,
+            bbox,
+        })
+    }
+    fn transform(&self, time: f64) -> Transform {
+        Transform::new(self.track.at(time))
+    }
+}
+
+// The box around every corner of `bbox` from the first to the last key, and
+// from 0 to 1 at least, as it holds still outside the keys. Between
+// two of the times it is checked at, a corner strays from both by at most
+// half the way it moves, so the box is padded by that.
+fn bounds(bbox: Aabb, track: &Track<9>) -> Aabb {
+    let start = track.times().fold(0.0, f64::min);
+    let end = track.times().fold(1.0, f64::max);
+    let mut times: Vec<f64> = (0..=BOUNDS_STEPS)
+        .map(|i| start + (end - start) * i as f64 / BOUNDS_STEPS as f64)
+        .chain(track.times())
+        .collect();
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let corners: Vec<Point3> = (0..8)
+        .map(|i| {
+            Point3::new(
+                if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+                if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+                if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+            )
+        })
+        .collect();
+
+    let inf = f64::INFINITY;
+    let mut min = Point3::new(inf, inf, inf);
+    let mut max = Point3::new(-inf, -inf, -inf);
+    let mut pad: f64 = 0.0;
+    let mut last: Option<Vec<Point3>> = None;
+    for time in times {
+        let transform = Transform::new(track.at(time));
+        let moved: Vec<Point3> = corners
+            .iter()
+            .map(|c| transform.point_to_world(*c))
+            .collect();
+        for p in moved.iter() {
+            for c in 0..3 {
+                min[c] = min[c].min(p[c]);
+                max[c] = max[c].max(p[c]);
+            }
+        }
+        if let Some(last) = &last {
+            for (a, b) in last.iter().zip(moved.iter()) {
+                pad = pad.max((*b - *a).length());
+            }
+        }
+        last = Some(moved);
+    }
+    let bbox = Aabb::two_point(&min, &max);
+    Aabb::new(bbox.x.expand(pad), bbox.y.expand(pad), bbox.z.expand(pad))
+}
+
+impl Hittable for Motion {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
+        let transform = self.transform(r.time());
+        let local = Ray::new(
+            &transform.point_to_object(r.origin()),
+            &transform.vector_to_object(r.direction()),
+            r.time(),
+        );
+        let mut rec = self.object.hit(&local, ray_t, rng)?;
+        rec.p = transform.point_to_world(rec.p);
+        rec.normal = transform.normal_to_world(rec.normal);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bbox.clone()
+    }
+
+    // Lights are sampled where they are at time 0. The density is the
+    // object's own, which is exact unless the object is scaled.
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let transform = self.transform(0.0);
+        self.object.pdf_value(
+            &transform.point_to_object(*origin),
+            &transform.vector_to_object(*direction),
+        )
+    }
+
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        let transform = self.transform(0.0);
+        let local = self.object.random(&transform.point_to_object(*origin), rng);
+        transform.vector_to_world(local)
+    }
+}
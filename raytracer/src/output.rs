@@ -0,0 +1,247 @@
+use crate::aov::Aov;
+use crate::exr::{write_exr, ExrChannel};
+use crate::framebuffer::FrameBuffer;
+use crate::metadata;
+use crate::tonemap::ToneMapping;
+use image::codecs::hdr::HdrEncoder;
+use image::codecs::pnm::{PNMSubtype, SampleEncoding};
+use image::{ImageOutputFormat, Rgb, RgbImage};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutputFormat {
+    Jpeg(u8),
+    Png,
+    Png16,
+    Ppm,
+    // the formats below keep the linear radiance
+    Hdr,
+    Pfm,
+    Exr,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str, quality: u8) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg(quality)),
+            "png" => Some(OutputFormat::Png),
+            "png16" => Some(OutputFormat::Png16),
+            "ppm" => Some(OutputFormat::Ppm),
+            "hdr" => Some(OutputFormat::Hdr),
+            "pfm" => Some(OutputFormat::Pfm),
+            "exr" => Some(OutputFormat::Exr),
+            _ => None,
+        }
+    }
+    pub fn from_path(path: &Path, quality: u8) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Self::from_name(ext, quality)
+    }
+    pub fn is_linear(&self) -> bool {
+        matches!(
+            self,
+            OutputFormat::Hdr | OutputFormat::Pfm | OutputFormat::Exr
+        )
+    }
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>, String> {
+    if let Some(prefix) = path.parent() {
+        std::fs::create_dir_all(prefix).map_err(|e| e.to_string())?;
+    }
+    let file =
+        File::create(path).map_err(|e| format!("Cannot create '{}': {}", path.display(), e))?;
+    Ok(BufWriter::new(file))
+}
+
+// 8-bit images, for the formats that can hold them
+pub fn write_rgb(path: &Path, img: &RgbImage, format: OutputFormat) -> Result<(), String> {
+    write_encoded(path, &encode_rgb(img, format)?, format, &[])
+}
+
+fn encode_rgb(img: &RgbImage, format: OutputFormat) -> Result<Vec<u8>, String> {
+    let output_format = match format {
+        OutputFormat::Jpeg(quality) => ImageOutputFormat::Jpeg(quality),
+        OutputFormat::Png => ImageOutputFormat::Png,
+        OutputFormat::Ppm => ImageOutputFormat::Pnm(PNMSubtype::Pixmap(SampleEncoding::Binary)),
+        _ => {
+            return Err(format!(
+                "{:?} cannot be written from an 8-bit image",
+                format
+            ))
+        }
+    };
+    let mut bytes = vec![];
+    image::DynamicImage::ImageRgb8(img.clone())
+        .write_to(&mut bytes, output_format)
+        .map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+// an encoded image with `metadata` added, where the format has room for it
+fn write_encoded(
+    path: &Path,
+    bytes: &[u8],
+    format: OutputFormat,
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let mut w = create(path)?;
+    w.write_all(&metadata::embed(bytes, format, metadata))
+        .and_then(|_| w.flush())
+        .map_err(|e| e.to_string())
+}
+
+// The 8 and 16-bit formats are tone mapped, the float formats keep the linear
+// radiance. `metadata` (name and value pairs, like the samples per pixel) ends
+// up in the header of every format but pfm.
+pub fn write(
+    path: &Path,
+    framebuffer: &FrameBuffer,
+    format: OutputFormat,
+    tone_mapping: &ToneMapping,
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Jpeg(_) | OutputFormat::Png | OutputFormat::Ppm => {
+            let bytes = encode_rgb(&framebuffer.to_rgb_image(tone_mapping), format)?;
+            write_encoded(path, &bytes, format, metadata)
+        }
+        OutputFormat::Png16 => {
+            let mut bytes = vec![];
+            image::DynamicImage::ImageRgb16(framebuffer.to_rgb16_image(tone_mapping))
+                .write_to(&mut bytes, ImageOutputFormat::Png)
+                .map_err(|e| e.to_string())?;
+            write_encoded(path, &bytes, format, metadata)
+        }
+        OutputFormat::Exr => write_layers(path, framebuffer, &[], metadata),
+        _ => write_linear(
+            path,
+            framebuffer.width(),
+            framebuffer.height(),
+            &framebuffer.to_linear(),
+            format,
+            metadata,
+        ),
+    }
+}
+
+// One AOV as an image of its own. The float formats get the raw values, the
+// others a picture of them (and `png16` an 8-bit one).
+pub fn write_aov(
+    path: &Path,
+    framebuffer: &FrameBuffer,
+    aov: Aov,
+    format: OutputFormat,
+) -> Result<(), String> {
+    match format {
+        OutputFormat::Png16 => write_rgb(path, &aov.to_rgb_image(framebuffer), OutputFormat::Png),
+        _ if !format.is_linear() => write_rgb(path, &aov.to_rgb_image(framebuffer), format),
+        _ => write_linear(
+            path,
+            framebuffer.width(),
+            framebuffer.height(),
+            &aov.to_linear(framebuffer),
+            format,
+            &[],
+        ),
+    }
+}
+
+// The beauty image as R, G, B and every AOV as a layer of its own, named like
+// `depth.Z` or `normal.X`, in one OpenEXR file
+pub fn write_layers(
+    path: &Path,
+    framebuffer: &FrameBuffer,
+    aovs: &[Aov],
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let pixels = framebuffer.to_linear();
+    let mut channels = rgb_channels(&pixels);
+    for aov in aovs {
+        let mut layer: Vec<ExrChannel> = aov
+            .channels()
+            .iter()
+            .map(|name| ExrChannel {
+                name: format!("{}.{}", aov.name(), name),
+                data: vec![],
+            })
+            .collect();
+        for j in 0..framebuffer.height() {
+            for i in 0..framebuffer.width() {
+                let values = aov.values(&framebuffer.features(i, j));
+                for (channel, value) in layer.iter_mut().zip(values) {
+                    channel.data.push(value as f32);
+                }
+            }
+        }
+        channels.append(&mut layer);
+    }
+    let mut w = create(path)?;
+    write_exr(
+        &mut w,
+        framebuffer.width(),
+        framebuffer.height(),
+        &mut channels,
+        metadata,
+    )
+    .map_err(|e| e.to_string())?;
+    w.flush().map_err(|e| e.to_string())
+}
+
+fn rgb_channels(pixels: &[Rgb<f32>]) -> Vec<ExrChannel> {
+    ["R", "G", "B"]
+        .iter()
+        .enumerate()
+        .map(|(c, name)| ExrChannel {
+            name: String::from(*name),
+            data: pixels.iter().map(|p| p[c]).collect(),
+        })
+        .collect()
+}
+
+// float pixels, row by row from the top, in one of the linear formats
+fn write_linear(
+    path: &Path,
+    width: u32,
+    height: u32,
+    pixels: &[Rgb<f32>],
+    format: OutputFormat,
+    metadata: &[(String, String)],
+) -> Result<(), String> {
+    let mut bytes = vec![];
+    match format {
+        OutputFormat::Hdr => HdrEncoder::new(&mut bytes)
+            .encode(pixels, width as usize, height as usize)
+            .map_err(|e| e.to_string())?,
+        OutputFormat::Pfm => {
+            write_pfm(&mut bytes, width, height, pixels).map_err(|e| e.to_string())?
+        }
+        OutputFormat::Exr => {
+            let mut channels = rgb_channels(pixels);
+            write_exr(&mut bytes, width, height, &mut channels, metadata)
+                .map_err(|e| e.to_string())?
+        }
+        _ => return Err(format!("{:?} is not a float format", format)),
+    }
+    write_encoded(path, &bytes, format, metadata)
+}
+
+// Portable float map: little endian (negative scale) and rows from the bottom up
+fn write_pfm<W: Write>(
+    w: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[Rgb<f32>],
+) -> std::io::Result<()> {
+    write!(w, "PF\n{} {}\n-1.0\n", width, height)?;
+    for row in pixels.chunks(width as usize).rev() {
+        for pixel in row {
+            for c in pixel.0.iter() {
+                w.write_all(&c.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,14 +1,14 @@
 use crate::hittable::Hittable;
 use crate::onb::Onb;
+use crate::rng::SampleRng;
 use crate::vec3::{dot, random_cosine_direction, random_unit_vector, unit_vector, Point3, Vec3};
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
 pub trait Pdf: Send + Sync {
     fn value(&self, _dir: &Vec3) -> f64 {
         0.0
     }
-    fn generate(&self) -> Vec3 {
+    fn generate(&self, _rng: &mut SampleRng) -> Vec3 {
         Vec3::black()
     }
 }
@@ -25,8 +25,8 @@ impl Pdf for SpherePDF {
     fn value(&self, _dir: &Vec3) -> f64 {
         1.0 / (4.0 * std::f64::consts::PI)
     }
-    fn generate(&self) -> Vec3 {
-        random_unit_vector()
+    fn generate(&self, rng: &mut SampleRng) -> Vec3 {
+        random_unit_vector(rng)
     }
 }
 
@@ -46,8 +46,8 @@ impl Pdf for CosinePDF {
         let cosine_theta = dot(&unit_vector(dir), &self.uvw.w());
         f64::max(0.0, cosine_theta / std::f64::consts::PI)
     }
-    fn generate(&self) -> Vec3 {
-        self.uvw.local(&random_cosine_direction())
+    fn generate(&self, rng: &mut SampleRng) -> Vec3 {
+        self.uvw.local(&random_cosine_direction(rng))
     }
 }
 
@@ -69,8 +69,8 @@ impl Pdf for HittablePDF {
     fn value(&self, dir: &Vec3) -> f64 {
         self.objects.pdf_value(&self.origin, dir)
     }
-    fn generate(&self) -> Vec3 {
-        self.objects.random(&self.origin)
+    fn generate(&self, rng: &mut SampleRng) -> Vec3 {
+        self.objects.random(&self.origin, rng)
     }
 }
 
@@ -89,11 +89,11 @@ impl Pdf for MixturePDF {
     fn value(&self, dir: &Vec3) -> f64 {
         0.5 * self.p[0].value(dir) + 0.5 * self.p[1].value(dir)
     }
-    fn generate(&self) -> Vec3 {
-        if thread_rng().gen_range(0.0..1.0) < 0.5 {
-            self.p[0].generate()
+    fn generate(&self, rng: &mut SampleRng) -> Vec3 {
+        if rng.get_1d() < 0.5 {
+            self.p[0].generate(rng)
         } else {
-            self.p[1].generate()
+            self.p[1].generate(rng)
         }
     }
 }
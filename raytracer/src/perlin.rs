@@ -1,5 +1,6 @@
+use crate::rng::SampleRng;
 use crate::vec3::{dot, Point3, Vec3};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 pub struct Perlin {
     rand_vec: Vec<Vec3>,
@@ -11,17 +12,17 @@ pub struct Perlin {
 impl Perlin {
     const POINT_COUNT: usize = 256;
 
-    pub fn new() -> Self {
+    pub fn new(rng: &mut SampleRng) -> Self {
         let mut rand_vec: Vec<Vec3> = vec![];
         for _i in 0..Self::POINT_COUNT {
-            rand_vec.push(Vec3::random_in(-1.0, 1.0));
+            rand_vec.push(Vec3::random_in(-1.0, 1.0, rng));
         }
 
         Self {
             rand_vec,
-            perm_x: Self::perlin_generate_perm(),
-            perm_y: Self::perlin_generate_perm(),
-            perm_z: Self::perlin_generate_perm(),
+            perm_x: Self::perlin_generate_perm(rng),
+            perm_y: Self::perlin_generate_perm(rng),
+            perm_z: Self::perlin_generate_perm(rng),
         }
     }
     pub fn noise(&self, p: &Point3) -> f64 {
@@ -63,17 +64,17 @@ impl Perlin {
 
         accum.abs()
     }
-    fn perlin_generate_perm() -> Vec<u32> {
+    fn perlin_generate_perm(rng: &mut SampleRng) -> Vec<u32> {
         let mut p: Vec<u32> = vec![];
         for i in 0..Self::POINT_COUNT {
             p.push(i as u32);
         }
-        Self::permute(&mut p, Self::POINT_COUNT);
+        Self::permute(&mut p, Self::POINT_COUNT, rng);
         p
     }
-    fn permute(p: &mut [u32], n: usize) {
+    fn permute(p: &mut [u32], n: usize, rng: &mut SampleRng) {
         for i in (1..n - 1).rev() {
-            let target = thread_rng().gen_range(0..i);
+            let target = rng.gen_range(0..i);
             p.swap(i, target);
         }
     }
@@ -0,0 +1,37 @@
+// A camera described like a real one. The focal length and the sensor set
+// the field of view, the aperture sets the depth of field, and together with
+// the shutter time and ISO it sets how bright the image comes out, so
+// emitters in cd/m² give properly exposed images.
+#[derive(Copy, Clone, Debug)]
+pub struct PhysicalCamera {
+    // in mm
+    pub focal_length: f64,
+    pub f_number: f64,
+    // in seconds, only for the exposure, motion blur follows the shutter
+    pub shutter_time: f64,
+    pub iso: f64,
+    // in mm, 36 for full frame
+    pub sensor_width: f64,
+    // scene units in a meter
+    pub units_per_meter: f64,
+}
+
+impl PhysicalCamera {
+    // the vertical field of view in degrees for a view `ratio` times as wide as high
+    pub fn vfov(&self, ratio: f64) -> f64 {
+        let sensor_height = self.sensor_width / ratio;
+        2.0 * (sensor_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+    // radius of the entrance pupil in scene units
+    pub fn aperture_radius(&self) -> f64 {
+        self.focal_length / self.f_number / 2.0 / 1000.0 * self.units_per_meter
+    }
+    // What the radiance is multiplied by. The saturation based sensitivity
+    // of ISO 12232 puts the brightest luminance a sensor records at
+    // 78 / (0.65 S) N² / t, and that becomes 1.
+    pub fn exposure(&self) -> f64 {
+        self.shutter_time * self.iso * 0.65 / (78.0 * self.f_number * self.f_number)
+    }
+}
@@ -0,0 +1,348 @@
+use crate::camera::Camera;
+use crate::framebuffer::FrameBuffer;
+use crate::sampler::SamplerKind;
+use crate::scene::Scene;
+use crate::tile::{Crop, Tile};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const MAGIC: &str = "raytracer checkpoint 1";
+
+// How a render is split into passes and when it stops
+pub struct Progressive {
+    // samples per pixel added by every pass
+    pub pass_samples: u32,
+    // stop after this many passes of this run
+    pub max_passes: Option<u32>,
+    // no pass is started that would not end within it
+    pub time_limit: Option<Duration>,
+    // Render for this long, however many samples per pixel that takes. After a
+    // first pass of `pass_samples` the passes are sized to about a tenth of
+    // it, and the last one to what is left.
+    pub budget: Option<Duration>,
+    pub checkpoint: Option<PathBuf>,
+    // the checkpoint is written after the first pass that ends this long after the last one
+    pub checkpoint_interval: Duration,
+}
+
+impl Progressive {
+    // everything in one pass, the plain render
+    pub fn single_pass(samples_per_pixel: u32) -> Self {
+        Self {
+            pass_samples: samples_per_pixel,
+            max_passes: None,
+            time_limit: None,
+            budget: None,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+// The settings a render was started with, so it can be resumed with the same
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RenderParams {
+    pub scene: String,
+    pub output: String,
+    pub image_width: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: i32,
+    pub seed: u64,
+    pub sampler: String,
+    pub noise_threshold: f64,
+    #[serde(default)]
+    pub crop: Option<Crop>,
+    #[serde(default)]
+    pub frame: Option<f64>,
+    pub passes: u32,
+    // seconds spent rendering, over every run
+    pub elapsed: f64,
+}
+
+impl RenderParams {
+    pub fn new(name: &str, scene: &Scene) -> Self {
+        let image = &scene.image_settings;
+        Self {
+            scene: String::from(name),
+            output: scene.output.clone(),
+            image_width: image.image_width,
+            samples_per_pixel: image.samples_per_pixel,
+            max_depth: image.max_depth,
+            seed: image.seed,
+            sampler: String::from(image.sampler.name()),
+            noise_threshold: image.noise_threshold,
+            crop: image.crop,
+            frame: scene.frame,
+            passes: 0,
+            elapsed: 0.0,
+        }
+    }
+    // sets up a freshly loaded scene like the checkpointed render
+    pub fn apply(&self, scene: &mut Scene) -> Result<(), String> {
+        let image = &mut scene.image_settings;
+        image.image_width = self.image_width;
+        image.samples_per_pixel = self.samples_per_pixel;
+        image.max_depth = self.max_depth;
+        image.seed = self.seed;
+        image.sampler = SamplerKind::from_name(&self.sampler)
+            .ok_or(format!("Unknown sampler '{}'", self.sampler))?;
+        image.noise_threshold = self.noise_threshold;
+        image.crop = self.crop;
+        if let Some(frame) = self.frame {
+            scene.set_frame(frame);
+        }
+        scene.output = self.output.clone();
+        Ok(())
+    }
+    // everything but the sample count has to match to continue a render
+    fn check_resume(&self, from: &RenderParams) -> Result<(), String> {
+        let settings = [
+            ("scene", from.scene.clone(), self.scene.clone()),
+            (
+                "width",
+                from.image_width.to_string(),
+                self.image_width.to_string(),
+            ),
+            (
+                "depth",
+                from.max_depth.to_string(),
+                self.max_depth.to_string(),
+            ),
+            ("seed", from.seed.to_string(), self.seed.to_string()),
+            ("sampler", from.sampler.clone(), self.sampler.clone()),
+            (
+                "noise threshold",
+                from.noise_threshold.to_string(),
+                self.noise_threshold.to_string(),
+            ),
+            (
+                "frame",
+                from.frame.map_or(String::from("none"), |f| f.to_string()),
+                self.frame.map_or(String::from("none"), |f| f.to_string()),
+            ),
+            (
+                "crop",
+                from.crop.map_or(String::from("none"), |c| c.to_string()),
+                self.crop.map_or(String::from("none"), |c| c.to_string()),
+            ),
+        ];
+        match settings.iter().find(|(_, then, now)| then != now) {
+            Some((what, then, now)) => Err(format!(
+                "The checkpoint was rendered with {} {}, not {}",
+                what, then, now
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+// A render that can be picked up later: its settings and the raw sums of
+// the framebuffer. The file is a line with the format, a line of JSON with
+// the settings and the framebuffer in binary.
+pub struct Checkpoint {
+    pub params: RenderParams,
+    pub framebuffer: FrameBuffer,
+}
+
+impl Checkpoint {
+    // the part of the framebuffer that is rendered
+    pub fn window(&self) -> Tile {
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        match self.params.crop {
+            Some(crop) => crop.window(width, height),
+            None => Tile {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            },
+        }
+    }
+    // the samples actually taken, averaged over the rendered pixels
+    pub fn samples_per_pixel(&self) -> f64 {
+        self.framebuffer.total_samples() as f64 / self.window().pixels().max(1) as f64
+    }
+    // what to note in the output files about how the render went
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        let mut metadata = vec![
+            (String::from("scene"), self.params.scene.clone()),
+            (
+                String::from("samplesPerPixel"),
+                format!("{:.2}", self.samples_per_pixel()),
+            ),
+            (String::from("passes"), self.params.passes.to_string()),
+            (
+                String::from("renderTime"),
+                format!("{:.2}s", self.params.elapsed),
+            ),
+        ];
+        if let Some(frame) = self.params.frame {
+            metadata.push((String::from("frame"), frame.to_string()));
+        }
+        metadata
+    }
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        save(path, &self.params, &self.framebuffer)
+    }
+    // the settings of the checkpointed render, to set the scene up by before loading it
+    pub fn params(path: &Path) -> Result<RenderParams, String> {
+        Ok(open(path)?.0)
+    }
+    // fails unless the image is `width` x `height`
+    pub fn load(path: &Path, width: u32, height: u32) -> Result<Self, String> {
+        let (params, mut r) = open(path)?;
+        let framebuffer = FrameBuffer::load(&mut r, width, height)
+            .map_err(|e| format!("Cannot read '{}': {}", path.display(), e))?;
+        Ok(Self {
+            params,
+            framebuffer,
+        })
+    }
+}
+
+// reads the settings and leaves the reader at the framebuffer
+fn open(path: &Path) -> Result<(RenderParams, BufReader<File>), String> {
+    let error = |e: std::io::Error| format!("Cannot read '{}': {}", path.display(), e);
+    let mut r = BufReader::new(File::open(path).map_err(error)?);
+    let mut line = String::new();
+    r.read_line(&mut line).map_err(error)?;
+    if line.trim_end() != MAGIC {
+        return Err(format!("'{}' is not a checkpoint", path.display()));
+    }
+    line.clear();
+    r.read_line(&mut line).map_err(error)?;
+    let params = serde_json::from_str(&line)
+        .map_err(|e| format!("Broken checkpoint '{}': {}", path.display(), e))?;
+    Ok((params, r))
+}
+
+// whether any rendered pixel is still above the noise threshold
+fn noisy(camera: &Camera) -> bool {
+    let window = camera.window;
+    (window.y..window.y + window.height).any(|j| {
+        (window.x..window.x + window.width)
+            .any(|i| camera.framebuffer.relative_error(i, j) > camera.noise_threshold)
+    })
+}
+
+// written next to the old one and moved over it, so a crash while saving
+// never loses the last checkpoint
+fn save(path: &Path, params: &RenderParams, framebuffer: &FrameBuffer) -> Result<(), String> {
+    let error = |e: std::io::Error| format!("Cannot write '{}': {}", path.display(), e);
+    if let Some(prefix) = path.parent() {
+        fs::create_dir_all(prefix).map_err(error)?;
+    }
+    let partial = path.with_extension("partial");
+    let mut w = BufWriter::new(File::create(&partial).map_err(error)?);
+    let json = serde_json::to_string(params).map_err(|e| e.to_string())?;
+    writeln!(w, "{}\n{}", MAGIC, json).map_err(error)?;
+    framebuffer.save(&mut w).map_err(error)?;
+    w.flush().map_err(error)?;
+    drop(w);
+    fs::rename(&partial, path).map_err(error)
+}
+
+// Renders `scene` in passes until it has its samples per pixel (or the budget
+// is spent) or a limit of `progressive` stops it, on top of the samples of
+// `resume` if there are any. `name` identifies the scene in the checkpoint.
+// The result holds everything rendered so far and can be saved to continue
+// later.
+pub fn render(
+    scene: &Scene,
+    name: &str,
+    progressive: &Progressive,
+    resume: Option<Checkpoint>,
+) -> Result<Checkpoint, String> {
+    let start = Instant::now();
+    let mut params = RenderParams::new(name, scene);
+    let features = scene.denoise.is_some() || !scene.aovs.is_empty();
+    let mut camera = Camera::new(scene.image_settings.clone(), scene.camera_settings.clone());
+    camera.features = features;
+    if let Some(resume) = resume {
+        params.check_resume(&resume.params)?;
+        let framebuffer = resume.framebuffer;
+        if framebuffer.width() != camera.image_width || framebuffer.height() != camera.image_height
+        {
+            return Err(String::from("The checkpoint does not match the image size"));
+        }
+        if features && !framebuffer.has_features() && framebuffer.total_samples() > 0 {
+            return Err(String::from(
+                "The checkpoint was rendered without the features for denoising and AOVs",
+            ));
+        }
+        params.passes = resume.params.passes;
+        params.elapsed = resume.params.elapsed;
+        camera.framebuffer = framebuffer;
+    }
+
+    let pixels = camera.window.pixels() as u64;
+    if pixels == 0 {
+        return Err(String::from("The crop window lies outside the image"));
+    }
+    let target = camera.samples_per_pixel as u64 * pixels;
+    let adaptive = camera.noise_threshold > 0.0;
+    let mut passes = 0;
+    let mut per_sample = Duration::ZERO;
+    let mut last_save = Instant::now();
+    loop {
+        if progressive.max_passes.map_or(false, |max| passes >= max) {
+            break;
+        }
+        let samples = match progressive.budget {
+            Some(budget) => {
+                let left = budget.saturating_sub(start.elapsed());
+                let fits = |time: Duration| match per_sample.as_secs_f64() {
+                    t if t > 0.0 => (time.as_secs_f64() / t) as u32,
+                    _ => u32::MAX,
+                };
+                match passes {
+                    0 => progressive.pass_samples,
+                    _ => fits(budget / 10).max(1).min(fits(left)),
+                }
+            }
+            None => {
+                let remaining = target.saturating_sub(camera.framebuffer.total_samples());
+                (progressive.pass_samples as u64).min((remaining + pixels - 1) / pixels) as u32
+            }
+        };
+        // adaptive passes only sample the pixels above the noise threshold
+        if samples == 0 || adaptive && !noisy(&camera) {
+            break;
+        }
+        if let Some(limit) = progressive.time_limit {
+            if passes > 0 && start.elapsed() + per_sample * samples > limit {
+                break;
+            }
+        }
+
+        let pass_start = Instant::now();
+        camera.render_pass(&scene.world, &scene.lights, samples);
+        per_sample = pass_start.elapsed() / samples;
+        passes += 1;
+        params.passes += 1;
+
+        if let Some(path) = &progressive.checkpoint {
+            if last_save.elapsed() >= progressive.checkpoint_interval {
+                let so_far = RenderParams {
+                    elapsed: params.elapsed + start.elapsed().as_secs_f64(),
+                    ..params.clone()
+                };
+                save(path, &so_far, &camera.framebuffer)?;
+                last_save = Instant::now();
+            }
+        }
+    }
+
+    params.elapsed += start.elapsed().as_secs_f64();
+    let checkpoint = Checkpoint {
+        params,
+        framebuffer: camera.framebuffer,
+    };
+    if let Some(path) = &progressive.checkpoint {
+        checkpoint.save(path)?;
+    }
+    Ok(checkpoint)
+}
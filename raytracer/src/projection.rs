@@ -0,0 +1,92 @@
+use crate::vec3::Vec3;
+
+// How the camera maps the image onto directions in the scene
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Projection {
+    // a pinhole or thin lens with a field of view of `vfov`
+    #[default]
+    Perspective,
+    // parallel rays from a view of this size in scene units. A size left out
+    // follows the aspect ratio of the image, without either the view is what
+    // `vfov` covers at the focus distance.
+    Orthographic {
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    // every direction around the camera, longitude across and latitude down
+    // the image, over these many degrees
+    Equirectangular {
+        longitude: f64,
+        latitude: f64,
+    },
+    // a circle of `fov` degrees across, as large as fits into the image
+    Fisheye {
+        fov: f64,
+        mapping: FisheyeMapping,
+    },
+}
+
+// how far from the middle of a fisheye image a direction `theta` off the
+// axis ends up
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    // in proportion to theta
+    Equidistant,
+    // in proportion to sin(theta / 2), keeping areas
+    Equisolid,
+}
+
+pub const FISHEYE_MAPPINGS: [&str; 2] = ["equidistant", "equisolid"];
+
+impl FisheyeMapping {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "equidistant" => Some(FisheyeMapping::Equidistant),
+            "equisolid" => Some(FisheyeMapping::Equisolid),
+            _ => None,
+        }
+    }
+}
+
+impl Projection {
+    // The direction through the point (x, y) of an image of `width` by
+    // `height` pixels, as amounts to the right, up and forwards. None when
+    // the point lies outside of what the projection covers. Only for the
+    // panoramic projections, the others go through the viewport.
+    pub fn direction(&self, x: f64, y: f64, width: f64, height: f64) -> Option<Vec3> {
+        match *self {
+            Projection::Equirectangular {
+                longitude,
+                latitude,
+            } => {
+                let phi = (x / width - 0.5) * longitude.to_radians();
+                let theta = (0.5 - y / height) * latitude.to_radians();
+                Some(Vec3::new(
+                    theta.cos() * phi.sin(),
+                    theta.sin(),
+                    theta.cos() * phi.cos(),
+                ))
+            }
+            Projection::Fisheye { fov, mapping } => {
+                let radius = width.min(height) / 2.0;
+                let (dx, dy) = ((x - width / 2.0) / radius, (height / 2.0 - y) / radius);
+                let r = (dx * dx + dy * dy).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+                let half = fov.to_radians() / 2.0;
+                let theta = match mapping {
+                    FisheyeMapping::Equidistant => r * half,
+                    FisheyeMapping::Equisolid => 2.0 * (r * (half / 2.0).sin()).asin(),
+                };
+                let phi = dy.atan2(dx);
+                Some(Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ))
+            }
+            Projection::Perspective | Projection::Orthographic { .. } => None,
+        }
+    }
+}
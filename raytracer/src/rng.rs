@@ -0,0 +1,111 @@
+use crate::sampler::Sampler;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg32;
+use std::sync::Arc;
+
+// the camera takes the pixel offset (2), the lens position (2) and the time (1)
+pub const CAMERA_DIMENSIONS: u32 = 5;
+// Every bounce reads from its own block of dimensions, so that a dimension
+// means the same thing in every path. Draws beyond the block fall back to
+// the plain generator.
+pub const BOUNCE_DIMENSIONS: u32 = 8;
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0, |h, v| splitmix64(h ^ v))
+}
+
+#[derive(Clone)]
+struct Stream {
+    sampler: Arc<dyn Sampler>,
+    i: u32,
+    j: u32,
+    index: u32,
+    dim: u32,
+    end: u32,
+}
+
+// The random numbers of one camera sample. `get_1d` and `get_2d` read the
+// next dimensions from the render's sampler, the `rand::Rng` methods use a
+// PCG generator for everything that does not need to be well distributed
+// (rejection sampling, building scenes). PCG is small, fast and its output
+// is fixed by the algorithm, so seeded renders stay reproducible.
+#[derive(Clone)]
+pub struct SampleRng {
+    rng: Pcg32,
+    stream: Option<Stream>,
+}
+
+impl SampleRng {
+    pub fn get_1d(&mut self) -> f64 {
+        if let Some(s) = &mut self.stream {
+            if s.dim < s.end {
+                s.dim += 1;
+                return s.sampler.get_1d(s.i, s.j, s.index, s.dim - 1);
+            }
+        }
+        self.rng.gen()
+    }
+    pub fn get_2d(&mut self) -> (f64, f64) {
+        if let Some(s) = &mut self.stream {
+            if s.dim + 1 < s.end {
+                s.dim += 2;
+                return s.sampler.get_2d(s.i, s.j, s.index, s.dim - 2);
+            }
+        }
+        (self.rng.gen(), self.rng.gen())
+    }
+    // `bounce` counts from 0 at the first hit
+    pub fn start_bounce(&mut self, bounce: u32) {
+        if let Some(s) = &mut self.stream {
+            s.dim = CAMERA_DIMENSIONS + bounce * BOUNCE_DIMENSIONS;
+            s.end = s.dim + BOUNCE_DIMENSIONS;
+        }
+    }
+}
+
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// Every sample of every pixel gets its own generator, derived only from the
+// seed and its coordinates, so it does not matter which thread renders it
+pub fn sample_rng(sampler: &Arc<dyn Sampler>, seed: u64, i: u32, j: u32, sample: u32) -> SampleRng {
+    let pixel = hash(&[seed, ((j as u64) << 32) | i as u64]);
+    SampleRng {
+        rng: Pcg32::new(splitmix64(pixel ^ sample as u64), pixel),
+        stream: Some(Stream {
+            sampler: sampler.clone(),
+            i,
+            j,
+            index: sample,
+            dim: 0,
+            end: CAMERA_DIMENSIONS,
+        }),
+    }
+}
+
+// for building scenes: random spheres, Perlin noise tables and so on
+pub fn seeded(seed: u64) -> SampleRng {
+    SampleRng {
+        rng: Pcg32::seed_from_u64(seed),
+        stream: None,
+    }
+}
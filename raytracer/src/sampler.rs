@@ -0,0 +1,403 @@
+use crate::rng::{self, hash};
+use rand::Rng;
+use std::sync::{Arc, OnceLock};
+
+// Supplies the sample dimensions of every pixel: the pixel offset, lens,
+// time and then a block per bounce (see rng.rs). A sampler is shared by all
+// threads, so every value is a pure function of its coordinates.
+pub trait Sampler: Send + Sync {
+    // dimension `dim` of sample `index` in pixel (i, j), in [0, 1)
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64;
+    // dimensions `dim` and `dim + 1`, well distributed as a pair
+    fn get_2d(&self, i: u32, j: u32, index: u32, dim: u32) -> (f64, f64) {
+        (
+            self.get_1d(i, j, index, dim),
+            self.get_1d(i, j, index, dim + 1),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SamplerKind {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+    BlueNoise,
+}
+
+pub const SAMPLERS: [&str; 5] = ["independent", "stratified", "halton", "sobol", "blue_noise"];
+
+impl SamplerKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "independent" => Some(SamplerKind::Independent),
+            "stratified" => Some(SamplerKind::Stratified),
+            "halton" => Some(SamplerKind::Halton),
+            "sobol" => Some(SamplerKind::Sobol),
+            "blue_noise" => Some(SamplerKind::BlueNoise),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            SamplerKind::Independent => "independent",
+            SamplerKind::Stratified => "stratified",
+            SamplerKind::Halton => "halton",
+            SamplerKind::Sobol => "sobol",
+            SamplerKind::BlueNoise => "blue_noise",
+        }
+    }
+}
+
+pub fn new(kind: SamplerKind, samples_per_pixel: u32, seed: u64) -> Arc<dyn Sampler> {
+    match kind {
+        SamplerKind::Independent => Arc::new(Independent { seed }),
+        SamplerKind::Stratified => Arc::new(Stratified {
+            samples_per_pixel: samples_per_pixel.max(1),
+            seed,
+        }),
+        SamplerKind::Halton => Arc::new(Halton::new(seed)),
+        SamplerKind::Sobol => Arc::new(Sobol { seed }),
+        SamplerKind::BlueNoise => Arc::new(BlueNoise { seed }),
+    }
+}
+
+fn to_unit(h: u64) -> f64 {
+    (h >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+fn pixel_key(i: u32, j: u32) -> u64 {
+    ((j as u64) << 32) | i as u64
+}
+
+// plain uniform random numbers, the baseline every other sampler is compared to
+pub struct Independent {
+    seed: u64,
+}
+
+impl Sampler for Independent {
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64 {
+        to_unit(hash(&[
+            self.seed,
+            pixel_key(i, j),
+            index as u64,
+            dim as u64,
+        ]))
+    }
+}
+
+// Andrew Kensler's hashed permutation of 0..l, from "Correlated Multi-Jittered Sampling"
+fn permute(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+// Jittered strata: one stratum per sample in 1D and a square grid in 2D,
+// visited in a random order per pixel and dimension. Samples beyond the
+// planned count start a new round of strata.
+pub struct Stratified {
+    samples_per_pixel: u32,
+    seed: u64,
+}
+
+impl Stratified {
+    // the stratum of `index` among `strata` and the seed for its jitter
+    fn stratum(&self, i: u32, j: u32, index: u32, dim: u32, strata: u32) -> (u32, u64) {
+        let round = index / strata;
+        let h = hash(&[self.seed, pixel_key(i, j), dim as u64, round as u64]);
+        let k = index % strata;
+        (permute(k, strata, h as u32), hash(&[h, k as u64]))
+    }
+}
+
+impl Sampler for Stratified {
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64 {
+        let strata = self.samples_per_pixel;
+        let (stratum, jitter) = self.stratum(i, j, index, dim, strata);
+        (stratum as f64 + to_unit(jitter)) / strata as f64
+    }
+    fn get_2d(&self, i: u32, j: u32, index: u32, dim: u32) -> (f64, f64) {
+        let n = (self.samples_per_pixel as f64).sqrt().ceil() as u32;
+        let (cell, jitter) = self.stratum(i, j, index, dim, n * n);
+        (
+            ((cell % n) as f64 + to_unit(jitter)) / n as f64,
+            ((cell / n) as f64 + to_unit(hash(&[jitter]))) / n as f64,
+        )
+    }
+}
+
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+// `permutations` shuffles the digits at every position, enough of them for any u32 index
+fn scrambled_radical_inverse(base: u32, permutations: &[Vec<u16>], mut index: u32) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let mut factor = inv_base;
+    let mut result = 0.0;
+    for permutation in permutations {
+        result += permutation[(index % base) as usize] as f64 * factor;
+        index /= base;
+        factor *= inv_base;
+    }
+    result
+}
+
+// The Halton sequence with one prime base per dimension. The digits are
+// randomly permuted, otherwise the large bases line up with each other for
+// the first samples, and every pixel is shifted by a random offset
+// (Cranley-Patterson rotation) so that neighbours do not share their errors.
+// Dimensions past the last prime are random.
+pub struct Halton {
+    seed: u64,
+    // per dimension, per digit position
+    permutations: Vec<Vec<Vec<u16>>>,
+}
+
+impl Halton {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = rng::seeded(seed);
+        let permutations = PRIMES
+            .iter()
+            .map(|&base| {
+                let mut digits = 0;
+                let mut reach = 1u64;
+                while reach <= u32::MAX as u64 {
+                    reach *= base as u64;
+                    digits += 1;
+                }
+                (0..digits)
+                    .map(|_| {
+                        let mut permutation: Vec<u16> = (0..base as u16).collect();
+                        for k in (1..permutation.len()).rev() {
+                            permutation.swap(k, rng.gen_range(0..=k));
+                        }
+                        permutation
+                    })
+                    .collect()
+            })
+            .collect();
+        Self { seed, permutations }
+    }
+}
+
+impl Sampler for Halton {
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64 {
+        let h = hash(&[self.seed, pixel_key(i, j), dim as u64]);
+        match PRIMES.get(dim as usize) {
+            Some(&base) => {
+                let x = scrambled_radical_inverse(base, &self.permutations[dim as usize], index);
+                (x + to_unit(h)).fract()
+            }
+            None => to_unit(hash(&[h, index as u64])),
+        }
+    }
+}
+
+// Owen scrambling as a hash, from Brent Burley's "Practical Hash-based Owen Scrambling"
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50b47c);
+    x ^= x.wrapping_mul(0xb82f1e52);
+    x ^= x.wrapping_mul(0xc7afe638);
+    x ^= x.wrapping_mul(0x8d22f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// the first two Sobol dimensions: van der Corput and the one from x + 1
+fn sobol(index: u32, dim: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut x = 0;
+    for bit in 0..32 {
+        if (index >> bit) & 1 == 1 {
+            x ^= if dim == 0 { 1 << (31 - bit) } else { v };
+        }
+        v ^= v >> 1;
+    }
+    x
+}
+
+fn sobol_unit(x: u32) -> f64 {
+    x as f64 * (1.0 / (1u64 << 32) as f64)
+}
+
+// Owen scrambled 2D Sobol points, padded to any number of dimensions by
+// shuffling the sample order differently for every pair of dimensions
+fn scrambled_sobol_2d(index: u32, seed: u64) -> (f64, f64) {
+    let index = nested_uniform_scramble(index, seed as u32);
+    let x = nested_uniform_scramble(sobol(index, 0), hash(&[seed, 0]) as u32);
+    let y = nested_uniform_scramble(sobol(index, 1), hash(&[seed, 1]) as u32);
+    (sobol_unit(x), sobol_unit(y))
+}
+
+pub struct Sobol {
+    seed: u64,
+}
+
+impl Sampler for Sobol {
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64 {
+        self.get_2d(i, j, index, dim).0
+    }
+    fn get_2d(&self, i: u32, j: u32, index: u32, dim: u32) -> (f64, f64) {
+        scrambled_sobol_2d(index, hash(&[self.seed, pixel_key(i, j), dim as u64]))
+    }
+}
+
+// Blue noise dithered sampling (Georgiev and Fajardo): every pixel uses the
+// same scrambled Sobol points, shifted by a blue noise mask. The error then
+// moves into high frequencies that the eye (and a denoiser) barely notice.
+pub struct BlueNoise {
+    seed: u64,
+}
+
+impl BlueNoise {
+    // a differently placed window of the mask for every dimension
+    fn offset(&self, i: u32, j: u32, dim: u32) -> f64 {
+        let h = hash(&[self.seed, dim as u64]);
+        let x = (i as usize + h as usize) % MASK_SIZE;
+        let y = (j as usize + (h >> 32) as usize) % MASK_SIZE;
+        blue_noise_mask()[y * MASK_SIZE + x] as f64
+    }
+}
+
+impl Sampler for BlueNoise {
+    fn get_1d(&self, i: u32, j: u32, index: u32, dim: u32) -> f64 {
+        self.get_2d(i, j, index, dim).0
+    }
+    fn get_2d(&self, i: u32, j: u32, index: u32, dim: u32) -> (f64, f64) {
+        let (x, y) = scrambled_sobol_2d(index, hash(&[self.seed, dim as u64]));
+        (
+            (x + self.offset(i, j, dim)).fract(),
+            (y + self.offset(i, j, dim + 1)).fract(),
+        )
+    }
+}
+
+const MASK_SIZE: usize = 64;
+
+fn blue_noise_mask() -> &'static [f32] {
+    static MASK: OnceLock<Vec<f32>> = OnceLock::new();
+    MASK.get_or_init(void_and_cluster)
+}
+
+// adds (or removes) the Gaussian energy of one point, wrapping around the edges
+fn splat(energy: &mut [f64], kernel: &[f64], p: usize, sign: f64) {
+    let (px, py) = (p % MASK_SIZE, p / MASK_SIZE);
+    for (q, e) in energy.iter_mut().enumerate() {
+        let dx = (q % MASK_SIZE + MASK_SIZE - px) % MASK_SIZE;
+        let dy = (q / MASK_SIZE + MASK_SIZE - py) % MASK_SIZE;
+        *e += sign * kernel[dy * MASK_SIZE + dx];
+    }
+}
+
+// the set (or unset) point with the highest (or lowest) energy
+fn extreme(energy: &[f64], pattern: &[bool], set: bool, highest: bool) -> usize {
+    let mut best = None;
+    for (p, &e) in energy.iter().enumerate() {
+        if pattern[p] != set {
+            continue;
+        }
+        best = match best {
+            Some((_, b)) if (highest && e <= b) || (!highest && e >= b) => best,
+            _ => Some((p, e)),
+        };
+    }
+    best.unwrap().0
+}
+
+// Robert Ulichney's void and cluster method: ranks every pixel of a tileable
+// mask so that each prefix of the ranking is evenly spread out
+fn void_and_cluster() -> Vec<f32> {
+    let len = MASK_SIZE * MASK_SIZE;
+    let sigma = 1.5;
+    let kernel: Vec<f64> = (0..len)
+        .map(|k| {
+            let dx = (k % MASK_SIZE).min(MASK_SIZE - k % MASK_SIZE) as f64;
+            let dy = (k / MASK_SIZE).min(MASK_SIZE - k / MASK_SIZE) as f64;
+            (-(dx * dx + dy * dy) / (2.0 * sigma * sigma)).exp()
+        })
+        .collect();
+
+    // a random initial pattern, relaxed by moving the tightest cluster into the largest void
+    let mut rng = rng::seeded(0);
+    let mut pattern = vec![false; len];
+    let mut energy = vec![0.0; len];
+    let initial = len / 10;
+    let mut count = 0;
+    while count < initial {
+        let p = rng.gen_range(0..len);
+        if !pattern[p] {
+            pattern[p] = true;
+            splat(&mut energy, &kernel, p, 1.0);
+            count += 1;
+        }
+    }
+    for _ in 0..len {
+        let cluster = extreme(&energy, &pattern, true, true);
+        pattern[cluster] = false;
+        splat(&mut energy, &kernel, cluster, -1.0);
+        let void = extreme(&energy, &pattern, false, false);
+        pattern[void] = true;
+        splat(&mut energy, &kernel, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0; len];
+    // the initial points, ranked by taking away the tightest clusters
+    let (mut shrinking, mut shrinking_energy) = (pattern.clone(), energy.clone());
+    for r in (0..initial).rev() {
+        let cluster = extreme(&shrinking_energy, &shrinking, true, true);
+        shrinking[cluster] = false;
+        splat(&mut shrinking_energy, &kernel, cluster, -1.0);
+        rank[cluster] = r;
+    }
+    // then the rest, filling the largest voids first
+    for r in initial..len {
+        let void = extreme(&energy, &pattern, false, false);
+        pattern[void] = true;
+        splat(&mut energy, &kernel, void, 1.0);
+        rank[void] = r;
+    }
+
+    rank.iter()
+        .map(|&r| (r as f32 + 0.5) / len as f32)
+        .collect()
+}
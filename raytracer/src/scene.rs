@@ -0,0 +1,139 @@
+use crate::animation::CameraTrack;
+use crate::aov::Aov;
+use crate::bvh::BvhNode;
+use crate::camera::{CameraSettings, ImageSettings};
+use crate::denoise::Denoise;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::tonemap::ToneMapping;
+use std::sync::Arc;
+
+const DEFAULT_OUTPUT: &str = "output/image.jpg";
+
+pub struct Scene {
+    pub world: HittableList,
+    pub lights: Option<Arc<dyn Hittable>>,
+    pub image_settings: ImageSettings,
+    pub camera_settings: CameraSettings,
+    // keyframes that move the camera from frame to frame
+    pub camera_track: Option<CameraTrack>,
+    // the frame the camera is set up for, none for a still
+    pub frame: Option<f64>,
+    pub tone_mapping: ToneMapping,
+    pub denoise: Option<Denoise>,
+    // written next to the image
+    pub aovs: Vec<Aov>,
+    pub output: String,
+}
+
+// an empty world seen by the default camera
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            world: HittableList::new(),
+            lights: None,
+            image_settings: ImageSettings::default(),
+            camera_settings: CameraSettings::default(),
+            camera_track: None,
+            frame: None,
+            tone_mapping: ToneMapping::default(),
+            denoise: None,
+            aovs: vec![],
+            output: String::from(DEFAULT_OUTPUT),
+        }
+    }
+}
+
+impl Scene {
+    // moves the camera to where its track has it at `frame`
+    pub fn set_frame(&mut self, frame: f64) {
+        if let Some(track) = &self.camera_track {
+            track.apply(frame, &mut self.camera_settings);
+        }
+        self.frame = Some(frame);
+    }
+}
+
+pub struct SceneBuilder {
+    world: HittableList,
+    lights: HittableList,
+    image_settings: ImageSettings,
+    camera_settings: CameraSettings,
+    camera_track: Option<CameraTrack>,
+    tone_mapping: ToneMapping,
+    denoise: Option<Denoise>,
+    aovs: Vec<Aov>,
+    output: String,
+}
+
+impl SceneBuilder {
+    pub fn new(image_settings: ImageSettings, camera_settings: CameraSettings) -> Self {
+        Self {
+            world: HittableList::new(),
+            lights: HittableList::new(),
+            image_settings,
+            camera_settings,
+            camera_track: None,
+            tone_mapping: ToneMapping::default(),
+            denoise: None,
+            aovs: vec![],
+            output: String::from(DEFAULT_OUTPUT),
+        }
+    }
+    pub fn add(&mut self, object: Arc<dyn Hittable>) -> &mut Self {
+        self.world.add(object);
+        self
+    }
+    // lights are only importance sampled, emitters still have to be added to the world
+    pub fn add_light(&mut self, object: Arc<dyn Hittable>) -> &mut Self {
+        self.lights.add(object);
+        self
+    }
+    pub fn camera_track(&mut self, track: CameraTrack) -> &mut Self {
+        self.camera_track = Some(track);
+        self
+    }
+    pub fn tone_mapping(&mut self, tone_mapping: ToneMapping) -> &mut Self {
+        self.tone_mapping = tone_mapping;
+        self
+    }
+    pub fn denoise(&mut self, denoise: Denoise) -> &mut Self {
+        self.denoise = Some(denoise);
+        self
+    }
+    pub fn aov(&mut self, aov: Aov) -> &mut Self {
+        if !self.aovs.contains(&aov) {
+            self.aovs.push(aov);
+        }
+        self
+    }
+    pub fn output(&mut self, path: &str) -> &mut Self {
+        self.output = String::from(path);
+        self
+    }
+    pub fn build(self) -> Scene {
+        let world = self.world;
+        let world = if world.objects.is_empty() {
+            world
+        } else {
+            HittableList::new_from(Arc::new(BvhNode::from_list(&mut world.with_object_ids())))
+        };
+        let lights: Option<Arc<dyn Hittable>> = if self.lights.objects.is_empty() {
+            None
+        } else {
+            Some(Arc::new(self.lights))
+        };
+        Scene {
+            world,
+            lights,
+            image_settings: self.image_settings,
+            camera_settings: self.camera_settings,
+            camera_track: self.camera_track,
+            frame: None,
+            tone_mapping: self.tone_mapping,
+            denoise: self.denoise,
+            aovs: self.aovs,
+            output: self.output,
+        }
+    }
+}
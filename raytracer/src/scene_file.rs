@@ -0,0 +1,942 @@
+use crate::animation::{self, CameraKey, CameraTrack, Interpolation};
+use crate::aov::{self, Aov};
+use crate::aperture::{Aperture, ApertureMask};
+use crate::bvh::BvhNode;
+use crate::camera::{CameraSettings, ImageSettings};
+use crate::denoise::Denoise;
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::lens::LensEffects;
+use crate::material::{Dielectric, DiffuseLight, Isotropic, Lambertian, Material, Metal};
+use crate::medium::ConstantMedium;
+use crate::motion::{Motion, MotionKey};
+use crate::obj::read_obj;
+use crate::physical::PhysicalCamera;
+use crate::projection::{self, FisheyeMapping, Projection};
+use crate::quad::{cuboid, Quad};
+use crate::rng::{self, SampleRng};
+use crate::sampler::SamplerKind;
+use crate::scene::{Scene, SceneBuilder};
+use crate::shutter::{Shutter, ShutterCurve};
+use crate::sphere::Sphere;
+use crate::stereo::{self, Stereo, StereoLayout};
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::tonemap::{self, ToneMapping, Transfer};
+use crate::translate::{RotateY, Translate};
+use crate::triangle::Triangle;
+use crate::vec3::Vec3;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+type Triple = [f64; 3];
+
+fn vec3(t: &Triple) -> Vec3 {
+    Vec3::new(t[0], t[1], t[2])
+}
+
+fn shutter(desc: &ShutterDesc) -> Result<Shutter, String> {
+    let curve = match &desc.curve {
+        CurveDesc::Name(name) => match name.as_str() {
+            "box" => ShutterCurve::constant(),
+            "triangle" => ShutterCurve::trapezoid(0.5, 0.5)?,
+            "trapezoid" => ShutterCurve::trapezoid(desc.ramp, desc.ramp)?,
+            _ => {
+                return Err(format!(
+                    "Unknown shutter curve '{}', use box, triangle, trapezoid or a list of values",
+                    name
+                ))
+            }
+        },
+        CurveDesc::Values(values) => ShutterCurve::tabulated(values)?,
+    };
+    Shutter::new(desc.open, desc.close, curve, desc.readout)
+}
+
+fn projection(desc: &ProjectionDesc) -> Result<Projection, String> {
+    Ok(match *desc {
+        ProjectionDesc::Perspective => Projection::Perspective,
+        ProjectionDesc::Orthographic { width, height } => {
+            if width.into_iter().chain(height).any(|size| size <= 0.0) {
+                return Err(String::from("An orthographic view needs a size above 0"));
+            }
+            Projection::Orthographic { width, height }
+        }
+        ProjectionDesc::Equirectangular {
+            longitude,
+            latitude,
+        } => {
+            if !(longitude > 0.0 && longitude <= 360.0 && latitude > 0.0 && latitude <= 180.0) {
+                return Err(String::from(
+                    "An equirectangular view covers up to 360 degrees of longitude and 180 of latitude",
+                ));
+            }
+            Projection::Equirectangular {
+                longitude,
+                latitude,
+            }
+        }
+        ProjectionDesc::Fisheye { fov, ref mapping } => {
+            if !(fov > 0.0 && fov <= 360.0) {
+                return Err(String::from("A fisheye covers up to 360 degrees"));
+            }
+            Projection::Fisheye {
+                fov,
+                mapping: FisheyeMapping::from_name(mapping).ok_or(format!(
+                    "Unknown fisheye mapping '{}', use {}",
+                    mapping,
+                    projection::FISHEYE_MAPPINGS.join(" or ")
+                ))?,
+            }
+        }
+    })
+}
+
+fn stereo(desc: &StereoDesc, camera: &CameraDesc) -> Result<Stereo, String> {
+    let convergence = desc.convergence.unwrap_or(camera.focus_dist);
+    if desc.interocular < 0.0 || convergence <= 0.0 {
+        return Err(String::from(
+            "Stereo needs an interocular distance of at least 0 and a convergence distance above 0",
+        ));
+    }
+    Ok(Stereo {
+        interocular: desc.interocular,
+        convergence,
+        layout: StereoLayout::from_name(&desc.layout).ok_or(format!(
+            "Unknown stereo layout '{}', use {}",
+            desc.layout,
+            stereo::STEREO_LAYOUTS.join(" or ")
+        ))?,
+    })
+}
+
+fn physical(desc: &PhysicalDesc) -> Result<PhysicalCamera, String> {
+    let values = [
+        desc.focal_length,
+        desc.f_number,
+        desc.shutter_time,
+        desc.iso,
+        desc.sensor_width,
+        desc.units_per_meter,
+    ];
+    if values.iter().any(|&v| v <= 0.0) {
+        return Err(String::from(
+            "A physical camera needs every one of its values above 0",
+        ));
+    }
+    Ok(PhysicalCamera {
+        focal_length: desc.focal_length,
+        f_number: desc.f_number,
+        shutter_time: desc.shutter_time,
+        iso: desc.iso,
+        sensor_width: desc.sensor_width,
+        units_per_meter: desc.units_per_meter,
+    })
+}
+
+fn aperture(desc: &ApertureDesc) -> Result<Aperture, String> {
+    Ok(match *desc {
+        ApertureDesc::Disk => Aperture::Disk,
+        ApertureDesc::Polygon { blades, rotation } => {
+            if blades < 3 {
+                return Err(String::from("An aperture polygon needs at least 3 blades"));
+            }
+            Aperture::Polygon { blades, rotation }
+        }
+        ApertureDesc::Catadioptric { obstruction } => {
+            if !(0.0..1.0).contains(&obstruction) {
+                return Err(String::from(
+                    "The obstruction of a catadioptric aperture must be at least 0 and below 1",
+                ));
+            }
+            Aperture::Catadioptric { obstruction }
+        }
+        ApertureDesc::Image { ref file } => {
+            if !Path::new(file).is_file() && !Path::new("images").join(file).is_file() {
+                return Err(format!("Cannot find the aperture image '{}'", file));
+            }
+            Aperture::Mask(Arc::new(ApertureMask::load(file)?))
+        }
+    })
+}
+
+fn lens(desc: &LensDesc, camera: &CameraDesc) -> Result<LensEffects, String> {
+    let panoramic = !matches!(
+        camera.projection,
+        ProjectionDesc::Perspective | ProjectionDesc::Orthographic { .. }
+    );
+    // it would trace every channel on its own for nothing
+    if panoramic && desc.chromatic_aberration != 0.0 {
+        return Err(String::from(
+            "Chromatic aberration needs a perspective or orthographic projection",
+        ));
+    }
+    if desc.chromatic_aberration.abs() >= 1.0 {
+        return Err(String::from(
+            "The chromatic aberration of a lens must be between -1 and 1",
+        ));
+    }
+    Ok(LensEffects {
+        distortion: desc.distortion,
+        vignetting: desc.vignetting,
+        chromatic_aberration: desc.chromatic_aberration,
+    })
+}
+
+fn parse_interpolation(name: &str) -> Result<Interpolation, String> {
+    Interpolation::from_name(name).ok_or(format!(
+        "Unknown interpolation '{}', use {}",
+        name,
+        animation::INTERPOLATIONS.join(" or ")
+    ))
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    #[serde(default)]
+    image: ImageDesc,
+    camera: CameraDesc,
+    animation: Option<AnimationDesc>,
+    #[serde(default)]
+    tone_mapping: ToneMappingDesc,
+    denoise: Option<DenoiseDesc>,
+    #[serde(default)]
+    aovs: Vec<String>,
+    output: Option<String>,
+    #[serde(default)]
+    textures: HashMap<String, TextureDesc>,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<ObjectDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ImageDesc {
+    aspect_ratio: f64,
+    image_width: u32,
+    quality: u8,
+    samples_per_pixel: u32,
+    max_depth: i32,
+    background: Triple,
+    threads: u32,
+    seed: u64,
+    sampler: String,
+    noise_threshold: f64,
+}
+
+impl Default for ImageDesc {
+    fn default() -> Self {
+        let image = ImageSettings::default();
+        Self {
+            aspect_ratio: image.aspect_ratio,
+            image_width: image.image_width,
+            quality: image.quality,
+            samples_per_pixel: image.samples_per_pixel,
+            max_depth: image.max_depth,
+            background: [image.background.x, image.background.y, image.background.z],
+            threads: image.threads,
+            seed: image.seed,
+            sampler: String::from(image.sampler.name()),
+            noise_threshold: image.noise_threshold,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ToneMappingDesc {
+    operator: String,
+    white: Option<f64>,
+    exposure: f64,
+    transfer: String,
+}
+
+impl Default for ToneMappingDesc {
+    fn default() -> Self {
+        Self {
+            operator: String::from("clamp"),
+            white: None,
+            exposure: 0.0,
+            transfer: String::from("gamma2"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DenoiseDesc {
+    strength: f64,
+    passes: u32,
+}
+
+impl Default for DenoiseDesc {
+    fn default() -> Self {
+        let denoise = Denoise::default();
+        Self {
+            strength: denoise.strength,
+            passes: denoise.passes,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    #[serde(default = "default_vfov")]
+    vfov: f64,
+    look_from: Triple,
+    look_at: Triple,
+    #[serde(default = "default_vup")]
+    vup: Triple,
+    #[serde(default)]
+    defocus_angle: f64,
+    #[serde(default = "default_focus_dist")]
+    focus_dist: f64,
+    #[serde(default)]
+    shutter: ShutterDesc,
+    #[serde(default)]
+    projection: ProjectionDesc,
+    stereo: Option<StereoDesc>,
+    physical: Option<PhysicalDesc>,
+    #[serde(default)]
+    aperture: ApertureDesc,
+    lens: Option<LensDesc>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LensDesc {
+    // k1, k2 and k3
+    distortion: [f64; 3],
+    vignetting: bool,
+    chromatic_aberration: f64,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ApertureDesc {
+    #[default]
+    Disk,
+    Polygon {
+        blades: u32,
+        #[serde(default)]
+        rotation: f64,
+    },
+    Catadioptric {
+        obstruction: f64,
+    },
+    Image {
+        file: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PhysicalDesc {
+    focal_length: f64,
+    f_number: f64,
+    shutter_time: f64,
+    iso: f64,
+    #[serde(default = "default_sensor_width")]
+    sensor_width: f64,
+    #[serde(default = "default_scale")]
+    units_per_meter: f64,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StereoDesc {
+    interocular: f64,
+    // the focus distance when left out
+    convergence: Option<f64>,
+    #[serde(default = "default_stereo_layout")]
+    layout: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ProjectionDesc {
+    #[default]
+    Perspective,
+    Orthographic {
+        width: Option<f64>,
+        height: Option<f64>,
+    },
+    Equirectangular {
+        #[serde(default = "default_longitude")]
+        longitude: f64,
+        #[serde(default = "default_latitude")]
+        latitude: f64,
+    },
+    Fisheye {
+        #[serde(default = "default_fisheye_fov")]
+        fov: f64,
+        #[serde(default = "default_fisheye_mapping")]
+        mapping: String,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ShutterDesc {
+    open: f64,
+    close: f64,
+    curve: CurveDesc,
+    // for a trapezoid, the part of the interval spent opening and again closing
+    ramp: f64,
+    // how much later the bottom row opens than the top one
+    readout: f64,
+}
+
+impl Default for ShutterDesc {
+    fn default() -> Self {
+        Self {
+            open: 0.0,
+            close: 1.0,
+            curve: CurveDesc::Name(String::from("box")),
+            ramp: 0.25,
+            readout: 0.0,
+        }
+    }
+}
+
+// a shutter curve by name or as efficiencies at evenly spaced times
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CurveDesc {
+    Name(String),
+    Values(Vec<f64>),
+}
+
+// keyframes of the camera, whatever a key leaves out stays as in `camera`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnimationDesc {
+    #[serde(default = "default_interpolation")]
+    interpolation: String,
+    keys: Vec<CameraKeyDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraKeyDesc {
+    frame: f64,
+    look_from: Option<Triple>,
+    look_at: Option<Triple>,
+    vfov: Option<f64>,
+    focus_dist: Option<f64>,
+    defocus_angle: Option<f64>,
+}
+
+fn default_interpolation() -> String {
+    String::from("spline")
+}
+fn default_motion_interpolation() -> String {
+    String::from("linear")
+}
+fn default_vfov() -> f64 {
+    40.0
+}
+fn default_longitude() -> f64 {
+    360.0
+}
+fn default_latitude() -> f64 {
+    180.0
+}
+fn default_fisheye_fov() -> f64 {
+    180.0
+}
+fn default_fisheye_mapping() -> String {
+    String::from("equidistant")
+}
+fn default_sensor_width() -> f64 {
+    36.0
+}
+fn default_stereo_layout() -> String {
+    String::from("side_by_side")
+}
+fn default_vup() -> Triple {
+    [0.0, 1.0, 0.0]
+}
+fn default_focus_dist() -> f64 {
+    10.0
+}
+fn default_scale() -> f64 {
+    1.0
+}
+fn default_true() -> bool {
+    true
+}
+
+// a texture can be named, given as a plain color or written inline
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureRef {
+    Name(String),
+    Color(Triple),
+    Inline(Box<TextureDesc>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TextureDesc {
+    Solid {
+        color: Triple,
+    },
+    Checker {
+        scale: f64,
+        even: TextureRef,
+        odd: TextureRef,
+    },
+    Image {
+        file: String,
+    },
+    Noise {
+        scale: f64,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialRef {
+    Name(String),
+    Inline(Box<MaterialDesc>),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    Metal { albedo: Triple, fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+    DiffuseLight { emit: TextureRef },
+    Isotropic { albedo: TextureRef },
+}
+
+// the pose of an animated object at a time of the shutter, 0 to 1
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MotionKeyDesc {
+    time: f64,
+    translate: Option<Triple>,
+    // degrees about x, y and z
+    rotate: Option<Triple>,
+    scale: Option<ScaleDesc>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScaleDesc {
+    Uniform(f64),
+    Axes(Triple),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: Triple,
+        radius: f64,
+        material: MaterialRef,
+        // end position of a moving sphere at time 1
+        center2: Option<Triple>,
+    },
+    Quad {
+        q: Triple,
+        u: Triple,
+        v: Triple,
+        material: MaterialRef,
+    },
+    Cuboid {
+        a: Triple,
+        b: Triple,
+        material: MaterialRef,
+    },
+    Triangle {
+        a: Triple,
+        b: Triple,
+        c: Triple,
+        material: MaterialRef,
+    },
+    Obj {
+        file: String,
+        #[serde(default = "default_scale")]
+        scale: f64,
+    },
+    Translate {
+        offset: Triple,
+        object: Box<ObjectDesc>,
+    },
+    RotateY {
+        angle: f64,
+        object: Box<ObjectDesc>,
+    },
+    Animated {
+        object: Box<ObjectDesc>,
+        #[serde(default = "default_motion_interpolation")]
+        interpolation: String,
+        keys: Vec<MotionKeyDesc>,
+    },
+    ConstantMedium {
+        boundary: Box<ObjectDesc>,
+        density: f64,
+        albedo: TextureRef,
+    },
+    List {
+        objects: Vec<ObjectDesc>,
+        #[serde(default = "default_true")]
+        bvh: bool,
+    },
+}
+
+struct Loader {
+    texture_descs: HashMap<String, TextureDesc>,
+    material_descs: HashMap<String, MaterialDesc>,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    // names being resolved right now, to report reference cycles instead of overflowing
+    pending: Vec<String>,
+    // noise tables are part of the scene, not of the render seed
+    rng: SampleRng,
+}
+
+impl Loader {
+    fn named_texture(&mut self, name: &str) -> Result<Arc<dyn Texture>, String> {
+        if let Some(tex) = self.textures.get(name) {
+            return Ok(tex.clone());
+        }
+        if self.pending.iter().any(|n| n == name) {
+            return Err(format!("Texture '{}' refers to itself", name));
+        }
+        let desc = match self.texture_descs.remove(name) {
+            Some(desc) => desc,
+            None => return Err(format!("Unknown texture '{}'", name)),
+        };
+        self.pending.push(String::from(name));
+        let tex = self.texture(&desc);
+        self.pending.pop();
+        self.texture_descs.insert(String::from(name), desc);
+        let tex = tex?;
+        self.textures.insert(String::from(name), tex.clone());
+        Ok(tex)
+    }
+
+    fn texture_ref(&mut self, r: &TextureRef) -> Result<Arc<dyn Texture>, String> {
+        match r {
+            TextureRef::Name(name) => self.named_texture(name),
+            TextureRef::Color(c) => Ok(Arc::new(SolidColor::new(&vec3(c)))),
+            TextureRef::Inline(desc) => self.texture(desc),
+        }
+    }
+
+    fn texture(&mut self, desc: &TextureDesc) -> Result<Arc<dyn Texture>, String> {
+        Ok(match desc {
+            TextureDesc::Solid { color } => Arc::new(SolidColor::new(&vec3(color))),
+            TextureDesc::Checker { scale, even, odd } => {
+                let even = self.texture_ref(even)?;
+                let odd = self.texture_ref(odd)?;
+                Arc::new(CheckerTexture::new(*scale, even, odd))
+            }
+            TextureDesc::Image { file } => {
+                // looked up the way RTWImage does, a missing one would only show in the render
+                if !Path::new(file).is_file() && !Path::new("images").join(file).is_file() {
+                    return Err(format!(
+                        "Texture image '{}' not found, neither as is nor under images/",
+                        file
+                    ));
+                }
+                Arc::new(ImageTexture::new(file))
+            }
+            TextureDesc::Noise { scale } => Arc::new(NoiseTexture::new(*scale, &mut self.rng)),
+        })
+    }
+
+    fn named_material(&mut self, name: &str) -> Result<Arc<dyn Material>, String> {
+        if let Some(mat) = self.materials.get(name) {
+            return Ok(mat.clone());
+        }
+        let desc = match self.material_descs.remove(name) {
+            Some(desc) => desc,
+            None => return Err(format!("Unknown material '{}'", name)),
+        };
+        let mat = self.material(&desc);
+        self.material_descs.insert(String::from(name), desc);
+        let mat = mat?;
+        self.materials.insert(String::from(name), mat.clone());
+        Ok(mat)
+    }
+
+    fn material_ref(&mut self, r: &MaterialRef) -> Result<Arc<dyn Material>, String> {
+        match r {
+            MaterialRef::Name(name) => self.named_material(name),
+            MaterialRef::Inline(desc) => self.material(desc),
+        }
+    }
+
+    fn material(&mut self, desc: &MaterialDesc) -> Result<Arc<dyn Material>, String> {
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo } => {
+                Arc::new(Lambertian::new_tex(self.texture_ref(albedo)?))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal::new(vec3(albedo), *fuzz)),
+            MaterialDesc::Dielectric { refraction_index } => {
+                Arc::new(Dielectric::new(*refraction_index))
+            }
+            MaterialDesc::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new_tex(self.texture_ref(emit)?))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                Arc::new(Isotropic::new_tex(self.texture_ref(albedo)?))
+            }
+        })
+    }
+
+    fn object(&mut self, desc: &ObjectDesc) -> Result<Arc<dyn Hittable>, String> {
+        Ok(match desc {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+                center2,
+            } => {
+                let mat = self.material_ref(material)?;
+                match center2 {
+                    Some(center2) => {
+                        Arc::new(Sphere::moving(&vec3(center), *radius, mat, &vec3(center2)))
+                    }
+                    None => Arc::new(Sphere::new(&vec3(center), *radius, mat)),
+                }
+            }
+            ObjectDesc::Quad { q, u, v, material } => Arc::new(Quad::new(
+                &vec3(q),
+                &vec3(u),
+                &vec3(v),
+                self.material_ref(material)?,
+            )),
+            ObjectDesc::Cuboid { a, b, material } => {
+                cuboid(&vec3(a), &vec3(b), self.material_ref(material)?)
+            }
+            ObjectDesc::Triangle { a, b, c, material } => Arc::new(Triangle::new(
+                &vec3(a),
+                &vec3(b),
+                &vec3(c),
+                self.material_ref(material)?,
+            )),
+            ObjectDesc::Obj { file, scale } => {
+                if !Path::new("objects").join(file).is_file() {
+                    return Err(format!("Cannot find 'objects/{}'", file));
+                }
+                Arc::new(read_obj(file, *scale))
+            }
+            ObjectDesc::Translate { offset, object } => {
+                Arc::new(Translate::new(self.object(object)?, &vec3(offset)))
+            }
+            ObjectDesc::RotateY { angle, object } => {
+                Arc::new(RotateY::new(self.object(object)?, *angle))
+            }
+            ObjectDesc::Animated {
+                object,
+                interpolation,
+                keys,
+            } => {
+                let keys: Vec<MotionKey> = keys
+                    .iter()
+                    .map(|key| {
+                        let mut k = MotionKey::at(key.time);
+                        k.translate = key.translate.as_ref().map_or(k.translate, vec3);
+                        k.rotate = key.rotate.as_ref().map_or(k.rotate, vec3);
+                        k.scale = match &key.scale {
+                            Some(ScaleDesc::Uniform(s)) => Vec3::new(*s, *s, *s),
+                            Some(ScaleDesc::Axes(s)) => vec3(s),
+                            None => k.scale,
+                        };
+                        k
+                    })
+                    .collect();
+                let motion = Motion::new(
+                    self.object(object)?,
+                    &keys,
+                    parse_interpolation(interpolation)?,
+                )
+                .map_err(|e| format!("Animated object: {}", e))?;
+                Arc::new(motion)
+            }
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                let boundary = self.object(boundary)?;
+                match albedo {
+                    TextureRef::Color(c) => {
+                        Arc::new(ConstantMedium::new(boundary, *density, &vec3(c)))
+                    }
+                    _ => {
+                        let tex = self.texture_ref(albedo)?;
+                        Arc::new(ConstantMedium::new_tex(boundary, *density, tex))
+                    }
+                }
+            }
+            ObjectDesc::List { objects, bvh } => {
+                let mut list = self.list(objects)?;
+                if *bvh && !list.objects.is_empty() {
+                    Arc::new(BvhNode::from_list(&mut list))
+                } else {
+                    Arc::new(list)
+                }
+            }
+        })
+    }
+
+    fn list(&mut self, descs: &[ObjectDesc]) -> Result<HittableList, String> {
+        let mut list = HittableList::new();
+        for desc in descs {
+            list.add(self.object(desc)?);
+        }
+        Ok(list)
+    }
+}
+
+pub fn load(path: &str) -> Result<Scene, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+    parse(&text, path)
+}
+
+// a scene description read from elsewhere, `path` names it in errors and the output
+pub fn parse(text: &str, path: &str) -> Result<Scene, String> {
+    let desc: SceneDesc =
+        serde_json::from_str(text).map_err(|e| format!("Invalid scene '{}': {}", path, e))?;
+
+    let mut loader = Loader {
+        texture_descs: desc.textures,
+        material_descs: desc.materials,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        pending: vec![],
+        rng: rng::seeded(0),
+    };
+
+    if desc.objects.is_empty() {
+        return Err(format!("Scene '{}' has no objects", path));
+    }
+
+    let image = desc.image;
+    if !(image.aspect_ratio.is_finite() && image.aspect_ratio > 0.0)
+        || image.image_width == 0
+        || image.samples_per_pixel == 0
+    {
+        return Err(format!(
+            "Invalid scene '{}': the image needs an aspect_ratio, image_width and samples_per_pixel above 0",
+            path
+        ));
+    }
+    let image_settings = ImageSettings {
+        aspect_ratio: image.aspect_ratio,
+        image_width: image.image_width,
+        quality: image.quality,
+        samples_per_pixel: image.samples_per_pixel,
+        max_depth: image.max_depth,
+        background: vec3(&image.background),
+        threads: image.threads,
+        seed: image.seed,
+        sampler: SamplerKind::from_name(&image.sampler)
+            .ok_or(format!("Unknown sampler '{}'", image.sampler))?,
+        noise_threshold: image.noise_threshold,
+        crop: None,
+    };
+
+    let camera = desc.camera;
+    let camera_settings = CameraSettings {
+        vfov: camera.vfov,
+        look_from: vec3(&camera.look_from),
+        look_at: vec3(&camera.look_at),
+        vup: vec3(&camera.vup),
+        defocus_angle: camera.defocus_angle,
+        focus_dist: camera.focus_dist,
+        shutter: shutter(&camera.shutter)?,
+        projection: projection(&camera.projection)?,
+        stereo: match &camera.stereo {
+            Some(desc) => Some(stereo(desc, &camera)?),
+            None => None,
+        },
+        physical: match &camera.physical {
+            Some(desc) => Some(physical(desc)?),
+            None => None,
+        },
+        aperture: aperture(&camera.aperture)?,
+        lens: match &camera.lens {
+            Some(desc) => Some(lens(desc, &camera)?),
+            None => None,
+        },
+    };
+    if camera_settings.stereo.is_some() {
+        Stereo::check(&camera_settings.projection)?;
+    }
+
+    let tone = desc.tone_mapping;
+    let tone_mapping = ToneMapping {
+        operator: tonemap::operator(&tone.operator, tone.white)
+            .ok_or(format!("Unknown tone mapping operator '{}'", tone.operator))?,
+        exposure: tone.exposure,
+        transfer: Transfer::from_name(&tone.transfer)
+            .ok_or(format!("Unknown transfer function '{}'", tone.transfer))?,
+    };
+
+    let output = desc.output.unwrap_or_else(|| {
+        let stem = Path::new(path).file_stem().unwrap().to_str().unwrap();
+        format!("output/{}.jpg", stem)
+    });
+
+    let track = match desc.animation {
+        Some(animation) => {
+            let interpolation = parse_interpolation(&animation.interpolation)?;
+            let keys = animation
+                .keys
+                .iter()
+                .map(|key| {
+                    let mut k = CameraKey::from_settings(key.frame, &camera_settings);
+                    k.look_from = key.look_from.as_ref().map_or(k.look_from, vec3);
+                    k.look_at = key.look_at.as_ref().map_or(k.look_at, vec3);
+                    k.vfov = key.vfov.unwrap_or(k.vfov);
+                    k.focus_dist = key.focus_dist.unwrap_or(k.focus_dist);
+                    k.defocus_angle = key.defocus_angle.unwrap_or(k.defocus_angle);
+                    k
+                })
+                .collect();
+            Some(CameraTrack::new(keys, interpolation)?)
+        }
+        None => None,
+    };
+
+    let mut builder = SceneBuilder::new(image_settings, camera_settings);
+    builder.tone_mapping(tone_mapping).output(&output);
+    if let Some(track) = track {
+        builder.camera_track(track);
+    }
+    for name in desc.aovs.iter() {
+        builder.aov(Aov::from_name(name).ok_or(format!(
+            "Unknown AOV '{}', use {}",
+            name,
+            aov::AOVS.join(", ")
+        ))?);
+    }
+    if let Some(denoise) = desc.denoise {
+        builder.denoise(Denoise {
+            strength: denoise.strength,
+            passes: denoise.passes,
+        });
+    }
+    for object in desc.objects.iter() {
+        builder.add(loader.object(object)?);
+    }
+    for light in desc.lights.iter() {
+        builder.add_light(loader.object(light)?);
+    }
+    Ok(builder.build())
+}
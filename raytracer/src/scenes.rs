@@ -0,0 +1,698 @@
+use rand::Rng;
+use raytracer::bvh::BvhNode;
+use raytracer::camera::{CameraSettings, ImageSettings};
+use raytracer::color::Color;
+use raytracer::hittable_list::HittableList;
+use raytracer::material::{Dielectric, DiffuseLight, Lambertian, Material, Metal};
+use raytracer::medium::ConstantMedium;
+use raytracer::obj::read_obj;
+use raytracer::quad::{cuboid, Quad};
+use raytracer::rng;
+use raytracer::scene::{Scene, SceneBuilder};
+use raytracer::sphere::Sphere;
+use raytracer::texture::{CheckerTexture, ImageTexture, NoiseTexture};
+use raytracer::translate::{RotateY, Translate};
+use raytracer::vec3::{Point3, Vec3};
+use std::sync::Arc;
+
+pub const SCENES: [&str; 7] = [
+    "bouncing_spheres",
+    "earth",
+    "perlin",
+    "quads",
+    "cornell_box",
+    "final_scene",
+    "book2_final",
+];
+
+pub fn by_name(name: &str) -> Option<Scene> {
+    match name {
+        "bouncing_spheres" => Some(bouncing_spheres()),
+        "earth" => Some(earth()),
+        "perlin" => Some(perlin()),
+        "quads" => Some(quads()),
+        "cornell_box" => Some(cornell_box()),
+        "final_scene" => Some(final_scene()),
+        "book2_final" => Some(book2_final()),
+        _ => None,
+    }
+}
+
+pub fn bouncing_spheres() -> Scene {
+    // Materials
+    let checker = Arc::new(CheckerTexture::new_color(
+        0.32,
+        &Color::new(0.2, 0.3, 0.1),
+        &Color::new(0.9, 0.9, 0.9),
+    ));
+    let material_ground = Arc::new(Lambertian::new_tex(checker));
+    let material1 = Arc::new(Dielectric::new(1.5));
+    let material2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
+    let material3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+
+    // World
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 3840,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            background: Color::new(0.7, 0.8, 1.0),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 20.0,
+            look_from: Point3::new(13.0, 2.0, 3.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            defocus_angle: 0.6,
+            ..Default::default()
+        },
+    );
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        material_ground,
+    )));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        material1,
+    )));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        material2,
+    )));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        material3,
+    )));
+
+    let mut rng = rng::seeded(0);
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rng.gen_range(0.0..1.0);
+            let center = Point3::new(
+                a as f64 + 0.9 * rng.gen_range(0.0..1.0),
+                0.2,
+                b as f64 + 0.9 * rng.gen_range(0.0..1.0),
+            );
+
+            if (center - Point3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                let sphere_material: Arc<dyn Material> = if choose_mat < 0.8 {
+                    let albedo = Color::random(&mut rng) * Color::random(&mut rng);
+                    Arc::new(Lambertian::new(albedo))
+                } else if choose_mat < 0.95 {
+                    let albedo = Color::random_in(0.5, 1.0, &mut rng);
+                    let fuzz = rng.gen_range(0.0..0.5);
+                    Arc::new(Metal::new(albedo, fuzz))
+                } else {
+                    Arc::new(Dielectric::new(1.5))
+                };
+                if choose_mat < 0.8 {
+                    let center2 = center + Vec3::new(0.0, rng.gen_range(0.0..0.5), 0.0);
+                    builder.add(Arc::new(Sphere::moving(
+                        &center,
+                        0.2,
+                        sphere_material,
+                        &center2,
+                    )));
+                } else {
+                    builder.add(Arc::new(Sphere::new(&center, 0.2, sphere_material)));
+                }
+            }
+        }
+    }
+
+    builder.output("output/book2/image2.jpg");
+    builder.build()
+}
+
+pub fn earth() -> Scene {
+    let earth_texture = Arc::new(ImageTexture::new("zbh.jpg"));
+    let earth_surface = Arc::new(Lambertian::new_tex(earth_texture));
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 1920,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            background: Color::new(0.7, 0.8, 1.0),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 20.0,
+            look_from: Point3::new(0.0, 0.0, 12.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            ..Default::default()
+        },
+    );
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, 0.0, 0.0),
+        2.0,
+        earth_surface,
+    )));
+
+    builder.output("output/book2/image5.jpg");
+    builder.build()
+}
+
+pub fn perlin() -> Scene {
+    let pertext = Arc::new(NoiseTexture::new(4.0, &mut rng::seeded(0)));
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            background: Color::new(0.7, 0.8, 1.0),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 20.0,
+            look_from: Point3::new(13.0, 2.0, 3.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            ..Default::default()
+        },
+    );
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Arc::new(Lambertian::new_tex(pertext.clone())),
+    )));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, 2.0, 0.0),
+        2.0,
+        Arc::new(Lambertian::new_tex(pertext)),
+    )));
+
+    builder.output("output/book2/image15.jpg");
+    builder.build()
+}
+
+pub fn quads() -> Scene {
+    let pertext = Arc::new(NoiseTexture::new(4.0, &mut rng::seeded(0)));
+    let left_red = Arc::new(Lambertian::new(Color::new(1.0, 0.2, 0.2)));
+    let back_green = Arc::new(Lambertian::new_tex(pertext));
+    let right_blue = Arc::new(Lambertian::new(Color::new(0.2, 0.2, 1.0)));
+    let upper_orange = Arc::new(Lambertian::new(Color::new(1.0, 0.5, 0.0)));
+    let lower_teal = Arc::new(Lambertian::new(Color::new(0.2, 0.8, 0.8)));
+
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 1.0,
+            image_width: 400,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            background: Color::new(0.7, 0.8, 1.0),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 80.0,
+            look_from: Point3::new(0.0, 0.0, 9.0),
+            look_at: Point3::new(0.0, 0.0, 0.0),
+            ..Default::default()
+        },
+    );
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(-3.0, -2.0, 5.0),
+        &Vec3::new(0.0, 0.0, -4.0),
+        &Vec3::new(0.0, 4.0, 0.0),
+        left_red,
+    )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(-2.0, -2.0, 0.0),
+        &Vec3::new(4.0, 0.0, 0.0),
+        &Vec3::new(0.0, 4.0, 0.0),
+        back_green,
+    )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(3.0, -2.0, 1.0),
+        &Vec3::new(0.0, 0.0, 4.0),
+        &Vec3::new(0.0, 4.0, 0.0),
+        right_blue,
+    )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(-2.0, 3.0, 1.0),
+        &Vec3::new(4.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 4.0),
+        upper_orange,
+    )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(-2.0, -3.0, 5.0),
+        &Vec3::new(4.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, -4.0),
+        lower_teal,
+    )));
+
+    builder.output("output/book2/image16.jpg");
+    builder.build()
+}
+
+pub fn cornell_box() -> Scene {
+    let diffuse = Arc::new(DiffuseLight::new(&Color::new(5.0, 5.0, 5.0)));
+    // let red = Arc::new(Lambertian::new(Color::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    // let green = Arc::new(Lambertian::new(Color::new(0.12, 0.45, 0.15)));
+
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 1.0,
+            image_width: 480,
+            samples_per_pixel: 25,
+            max_depth: 20,
+            background: Color::black(),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 40.0,
+            look_from: Point3::new(278.0, 400.0, -800.0),
+            look_at: Point3::new(278.0, 278.0, 0.0),
+            ..Default::default()
+        },
+    );
+    // builder.add(Arc::new(Quad::new(
+    //     &Point3::new(555.0, 0.0, 0.0),
+    //     &Vec3::new(0.0, 555.0, 0.0),
+    //     &Vec3::new(0.0, 0.0, 555.0),
+    //     green,
+    // )));
+    // builder.add(Arc::new(Quad::new(
+    //     &Point3::new(0.0, 0.0, 0.0),
+    //     &Vec3::new(2.0, 555.0, 0.0),
+    //     &Vec3::new(0.0, 0.0, 555.0),
+    //     red,
+    // )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 554.0, 0.0),
+        &Vec3::new(555.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 555.0),
+        diffuse.clone(),
+    )));
+    builder.add_light(Arc::new(Quad::new(
+        &Point3::new(0.0, 554.0, 0.0),
+        &Vec3::new(555.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 555.0),
+        diffuse,
+    )));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(-500.0, 0.0, -200.0),
+        &Vec3::new(2000.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 1555.0),
+        white,
+    )));
+    // builder.add(Arc::new(Quad::new(
+    //     &Point3::new(555.0, 555.0, 555.0),
+    //     &Vec3::new(-555.0, 0.0, 0.0),
+    //     &Vec3::new(0.0, 0.0, -555.0),
+    //     white.clone(),
+    // )));
+    // builder.add(Arc::new(Quad::new(
+    //     &Point3::new(0.0, 0.0, 555.0),
+    //     &Vec3::new(555.0, 0.0, 0.0),
+    //     &Vec3::new(0.0, 555.0, 0.0),
+    //     white,
+    // )));
+
+    let obj = read_obj("ps1.obj", 1.0);
+    let obj = RotateY::new(Arc::new(obj), 30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(256.0, 100.0, 0.0));
+    builder.add(Arc::new(obj));
+    // builder.add(Arc::new(Triangle::new(
+    //     &Point3::new(0.0, 0.0, 400.0),
+    //     &Point3::new(256.0, 0.0, 555.0),
+    //     &Point3::new(0.0, 256.0, 555.0),
+    //     white,
+    // )));
+
+    builder.output("output/advanced/image2.jpg");
+    builder.build()
+}
+
+pub fn final_scene() -> Scene {
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 16.0 / 9.0,
+            image_width: 1920,
+            samples_per_pixel: 1200,
+            max_depth: 30,
+            background: Color::black(),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 40.0,
+            look_from: Point3::new(800.0, 450.0, -800.0),
+            look_at: Point3::new(800.0, 450.0, 0.0),
+            ..Default::default()
+        },
+    );
+    let mut lights = HittableList::new();
+
+    let stadium = Arc::new(Lambertian::new_tex(Arc::new(ImageTexture::new(
+        "Stadium.jpg",
+    ))));
+    let sky_box = Arc::new(Sphere::new(&Point3::new(0.0, 400.0, 0.0), 2000.0, stadium));
+    let sky_box = RotateY::new(sky_box, -58.5);
+    builder.add(Arc::new(sky_box));
+
+    let diffuse = Arc::new(DiffuseLight::new(&Color::new(8.0, 8.0, 8.0)));
+
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 450.0, -810.0),
+        &Vec3::new(800.0, 0.0, 0.0),
+        &Vec3::new(0.0, 450.0, 0.0),
+        diffuse.clone(),
+    )));
+    lights.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 450.0, -810.0),
+        &Vec3::new(800.0, 0.0, 0.0),
+        &Vec3::new(0.0, 450.0, 0.0),
+        diffuse,
+    )));
+
+    let diffuse = Arc::new(DiffuseLight::new(&Color::new(3.0, 3.0, 3.0)));
+
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 1600.0, 0.0),
+        &Vec3::new(1600.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 800.0),
+        diffuse.clone(),
+    )));
+    lights.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 1600.0, 0.0),
+        &Vec3::new(1600.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 800.0),
+        diffuse,
+    )));
+
+    let obj = read_obj("title.obj", 300.0);
+    let obj = RotateY::new(Arc::new(obj), 0.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1540.0, 700.0, 600.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("pokeball.obj", 200.0);
+    let obj = RotateY::new(Arc::new(obj), 0.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(800.0, 370.0, 100.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("field.obj", 1000.0);
+    let obj = RotateY::new(Arc::new(obj), 0.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(800.0, 170.0, 300.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Cubone.obj", 300.0);
+    let obj = RotateY::new(Arc::new(obj), 45.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1050.0, 300.0, 200.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Pikachu.obj", 120.0);
+    let obj = RotateY::new(Arc::new(obj), 0.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(800.0, 250.0, -50.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Hat.obj", 120.0);
+    let obj = RotateY::new(Arc::new(obj), -10.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(797.0, 285.0, -50.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Margikarp.obj", 200.0);
+    let obj = RotateY::new(Arc::new(obj), 0.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1100.0, 200.0, 50.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Snorlax.obj", 500.0);
+    let obj = RotateY::new(Arc::new(obj), -30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(500.0, 300.0, 300.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Haunter.obj", 350.0);
+    let obj = RotateY::new(Arc::new(obj), 30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1200.0, 350.0, 300.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Bulbasaur.obj", 150.0);
+    let obj = RotateY::new(Arc::new(obj), -20.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(700.0, 200.0, 0.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Squirtle.obj", 320.0);
+    let obj = RotateY::new(Arc::new(obj), 85.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(900.0, 240.0, 0.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Charmander.obj", 150.0);
+    let obj = RotateY::new(Arc::new(obj), -30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(580.0, 220.0, 0.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Jigglypuff.obj", 50.0);
+    let obj = RotateY::new(Arc::new(obj), 30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1000.0, 200.0, 0.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Marill.obj", 200.0);
+    let obj = RotateY::new(Arc::new(obj), 45.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(450.0, 220.0, 50.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Bellsprout.obj", 100.0);
+    let obj = RotateY::new(Arc::new(obj), 60.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1250.0, 220.0, 150.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Eevee.obj", 180.0);
+    let obj = RotateY::new(Arc::new(obj), -30.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(380.0, 270.0, 80.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Magnemite.obj", 100.0);
+    let obj = RotateY::new(Arc::new(obj), -20.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(350.0, 400.0, 90.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Oddish.obj", 200.0);
+    let obj = RotateY::new(Arc::new(obj), 45.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(980.0, 280.0, 320.0));
+    builder.add(Arc::new(obj));
+
+    let obj = read_obj("Poliwag.obj", 200.0);
+    let obj = RotateY::new(Arc::new(obj), 45.0);
+    let obj = Translate::new(Arc::new(obj), &Vec3::new(1200.0, 200.0, 300.0));
+    builder.add(Arc::new(obj));
+
+    let advertise = Arc::new(Lambertian::new_tex(Arc::new(ImageTexture::new(
+        "Advertisement.jpg",
+    ))));
+
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(1600.0, 50.0, 800.0),
+        &Vec3::new(-1600.0, 0.0, 0.0),
+        &Vec3::new(0.0, 250.0, 0.0),
+        advertise.clone(),
+    )));
+
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(0.0, 50.0, 800.0),
+        &Vec3::new(0.0, 0.0, -800.0),
+        &Vec3::new(0.0, 250.0, 0.0),
+        advertise.clone(),
+    )));
+
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(1600.0, 50.0, 0.0),
+        &Vec3::new(0.0, 0.0, 800.0),
+        &Vec3::new(0.0, 250.0, 0.0),
+        advertise,
+    )));
+
+    builder.add_light(Arc::new(BvhNode::from_list(&mut lights)));
+
+    builder.output("output/advanced/image4.jpg");
+    builder.build()
+}
+
+pub fn book2_final() -> Scene {
+    let mut rng = rng::seeded(0);
+    let mut boxes1 = HittableList::new();
+    let ground = Arc::new(Lambertian::new(Color::new(0.48, 0.83, 0.53)));
+
+    let boxes_per_sides = 20;
+    for i in 0..boxes_per_sides {
+        for j in 0..boxes_per_sides {
+            let w = 100.0;
+            let x0 = -1000.0 + i as f64 * w;
+            let y0 = 0.0;
+            let z0 = -1000.0 + j as f64 * w;
+            let x1 = x0 + w;
+            let y1 = rng.gen_range(1.0..101.0);
+            let z1 = z0 + w;
+
+            boxes1.add(cuboid(
+                &Point3::new(x0, y0, z0),
+                &Point3::new(x1, y1, z1),
+                ground.clone(),
+            ));
+        }
+    }
+
+    let mut builder = SceneBuilder::new(
+        ImageSettings {
+            aspect_ratio: 1.0,
+            image_width: 600,
+            samples_per_pixel: 2500,
+            max_depth: 40,
+            background: Color::black(),
+            ..Default::default()
+        },
+        CameraSettings {
+            vfov: 40.0,
+            look_from: Point3::new(478.0, 278.0, -600.0),
+            look_at: Point3::new(278.0, 278.0, 0.0),
+            ..Default::default()
+        },
+    );
+    builder.add(Arc::new(BvhNode::from_list(&mut boxes1)));
+
+    let light = Arc::new(DiffuseLight::new(&Color::new(15.0, 15.0, 15.0)));
+    builder.add(Arc::new(Quad::new(
+        &Point3::new(123.0, 554.0, 147.0),
+        &Vec3::new(300.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 265.0),
+        light.clone(),
+    )));
+    builder.add_light(Arc::new(Quad::new(
+        &Point3::new(123.0, 554.0, 147.0),
+        &Vec3::new(300.0, 0.0, 0.0),
+        &Vec3::new(0.0, 0.0, 265.0),
+        light,
+    )));
+
+    let center1 = Point3::new(400.0, 400.0, 200.0);
+    let center2 = center1 + Vec3::new(30.0, 0.0, 0.0);
+    let sphere_material = Arc::new(Lambertian::new(Color::new(0.7, 0.3, 0.1)));
+    builder.add(Arc::new(Sphere::moving(
+        &center1,
+        50.0,
+        sphere_material,
+        &center2,
+    )));
+
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(260.0, 150.0, 45.0),
+        50.0,
+        Arc::new(Dielectric::new(1.5)),
+    )));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(0.0, 150.0, 145.0),
+        50.0,
+        Arc::new(Metal::new(Color::new(0.8, 0.8, 0.9), 1.0)),
+    )));
+
+    let mut boundary = Arc::new(Sphere::new(
+        &Point3::new(360.0, 150.0, 145.0),
+        70.0,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    builder.add(boundary.clone());
+    builder.add(Arc::new(ConstantMedium::new(
+        boundary,
+        0.2,
+        &Color::new(0.2, 0.4, 0.9),
+    )));
+    boundary = Arc::new(Sphere::new(
+        &Point3::new(0.0, 0.0, 0.0),
+        5000.0,
+        Arc::new(Dielectric::new(1.5)),
+    ));
+    builder.add(Arc::new(ConstantMedium::new(
+        boundary,
+        0.0001,
+        &Color::white(),
+    )));
+
+    let e_mat = Arc::new(Lambertian::new_tex(Arc::new(ImageTexture::new(
+        "earthmap.jpg",
+    ))));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(400.0, 200.0, 400.0),
+        100.0,
+        e_mat,
+    )));
+    let per_text = Arc::new(NoiseTexture::new(0.2, &mut rng));
+    builder.add(Arc::new(Sphere::new(
+        &Point3::new(220.0, 280.0, 300.0),
+        80.0,
+        Arc::new(Lambertian::new_tex(per_text)),
+    )));
+
+    let mut boxes2 = HittableList::new();
+    let white = Arc::new(Lambertian::new(Color::new(0.73, 0.73, 0.73)));
+    let ns = 1000;
+    for _j in 0..ns {
+        boxes2.add(Arc::new(Sphere::new(
+            &Point3::random_in(0.0, 165.0, &mut rng),
+            10.0,
+            white.clone(),
+        )));
+    }
+
+    builder.add(Arc::new(Translate::new(
+        Arc::new(RotateY::new(
+            Arc::new(BvhNode::from_list(&mut boxes2)),
+            15.0,
+        )),
+        &Vec3::new(-100.0, 270.0, 395.0),
+    )));
+
+    builder.output("output/book2/image23.jpg");
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raytracer::camera::Camera;
+
+    // the summed radiance and sample count of every pixel, bit for bit
+    fn render(threads: u32, seed: u64) -> Vec<[u64; 4]> {
+        let scene = bouncing_spheres();
+        let image_settings = ImageSettings {
+            image_width: 64,
+            samples_per_pixel: 2,
+            max_depth: 4,
+            threads,
+            seed,
+            ..scene.image_settings
+        };
+        let mut camera = Camera::new(image_settings, scene.camera_settings);
+        camera.render(scene.world, scene.lights);
+        let framebuffer = &camera.framebuffer;
+        let mut pixels = vec![];
+        for j in 0..framebuffer.height() {
+            for i in 0..framebuffer.width() {
+                let sum = framebuffer.sum(i, j);
+                pixels.push([
+                    sum.x.to_bits(),
+                    sum.y.to_bits(),
+                    sum.z.to_bits(),
+                    framebuffer.samples(i, j) as u64,
+                ]);
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn same_seed_renders_the_same_at_any_thread_count() {
+        let single = render(1, 7);
+        for threads in [2, 3, 7] {
+            assert!(render(threads, 7) == single, "{} threads differ", threads);
+        }
+    }
+
+    #[test]
+    fn another_seed_renders_differently() {
+        assert!(render(4, 7) != render(4, 8));
+    }
+}
@@ -0,0 +1,119 @@
+// How much light the shutter lets through while it opens, stays open and
+// closes, as efficiencies at points from 0 (opening) to 1 (closed), linear
+// in between. Ray times are drawn in proportion to it.
+#[derive(Clone, Debug)]
+pub struct ShutterCurve {
+    points: Vec<(f64, f64)>,
+    // area under the curve up to every point
+    cdf: Vec<f64>,
+}
+
+impl ShutterCurve {
+    // fully open the whole time
+    pub fn constant() -> Self {
+        Self::new(vec![(0.0, 1.0), (1.0, 1.0)])
+    }
+    // spends the first `opening` and the last `closing` of the interval
+    // ramping up and down
+    pub fn trapezoid(opening: f64, closing: f64) -> Result<Self, String> {
+        if opening < 0.0 || closing < 0.0 || opening + closing > 1.0 {
+            return Err(String::from(
+                "The shutter ramps must be at least 0 and add up to at most 1",
+            ));
+        }
+        Ok(Self::new(vec![
+            (0.0, 0.0),
+            (opening, 1.0),
+            (1.0 - closing, 1.0),
+            (1.0, 0.0),
+        ]))
+    }
+    // efficiencies at evenly spaced times
+    pub fn tabulated(values: &[f64]) -> Result<Self, String> {
+        if values.len() < 2 {
+            return Err(String::from("A shutter curve needs at least two values"));
+        }
+        if values.iter().any(|&v| v < 0.0) || values.iter().all(|&v| v == 0.0) {
+            return Err(String::from(
+                "A shutter curve needs values of at least 0 and one above 0",
+            ));
+        }
+        let last = (values.len() - 1) as f64;
+        Ok(Self::new(
+            values
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| (i as f64 / last, v))
+                .collect(),
+        ))
+    }
+    fn new(points: Vec<(f64, f64)>) -> Self {
+        let mut cdf = vec![0.0];
+        for pair in points.windows(2) {
+            let ((x0, e0), (x1, e1)) = (pair[0], pair[1]);
+            cdf.push(cdf[cdf.len() - 1] + (e0 + e1) / 2.0 * (x1 - x0));
+        }
+        Self { points, cdf }
+    }
+    // maps a uniform `u` in [0, 1) to a fraction of the interval
+    pub fn sample(&self, u: f64) -> f64 {
+        let target = u * self.cdf[self.cdf.len() - 1];
+        // the segment holding `target`, skipping any without area
+        let k = (self.cdf.partition_point(|&c| c <= target).max(1) - 1).min(self.points.len() - 2);
+        let ((x0, e0), (x1, e1)) = (self.points[k], self.points[k + 1]);
+        let width = x1 - x0;
+        let area = target - self.cdf[k];
+        // solves e0 s + (e1 - e0) s^2 / (2 width) = area for s
+        let a = (e1 - e0) / (2.0 * width);
+        let root = e0 + (e0 * e0 + 4.0 * a * area).max(0.0).sqrt();
+        let s = if root > 0.0 { 2.0 * area / root } else { 0.0 };
+        x0 + s.clamp(0.0, width)
+    }
+}
+
+// When the shutter opens and closes, in the time of moving objects, which
+// move between 0 and 1. A rolling shutter opens row by row, the last row
+// `readout` later than the first.
+#[derive(Clone, Debug)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+    pub curve: ShutterCurve,
+    pub readout: f64,
+}
+
+impl Default for Shutter {
+    fn default() -> Self {
+        Self {
+            open: 0.0,
+            close: 1.0,
+            curve: ShutterCurve::constant(),
+            readout: 0.0,
+        }
+    }
+}
+
+impl Shutter {
+    pub fn new(open: f64, close: f64, curve: ShutterCurve, readout: f64) -> Result<Self, String> {
+        if close < open {
+            return Err(format!(
+                "The shutter closes at {} before it opens at {}",
+                close, open
+            ));
+        }
+        if readout < 0.0 {
+            return Err(String::from("The shutter readout cannot be negative"));
+        }
+        Ok(Self {
+            open,
+            close,
+            curve,
+            readout,
+        })
+    }
+    // the time of a sample at `row`, 0 at the top of the image and 1 at the
+    // bottom, from a uniform `u`
+    pub fn time(&self, u: f64, row: f64) -> f64 {
+        self.open + self.readout * row + (self.close - self.open) * self.curve.sample(u)
+    }
+}
@@ -4,8 +4,8 @@ use crate::interval::Interval;
 use crate::material::Material;
 use crate::onb::Onb;
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::vec3::{dot, Point3, Vec3};
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
 pub struct Sphere {
@@ -18,7 +18,7 @@ pub struct Sphere {
 }
 
 impl Sphere {
-    pub(crate) fn new(center: &Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
+    pub fn new(center: &Point3, radius: f64, mat: Arc<dyn Material>) -> Self {
         let r_vec = Vec3::new(radius, radius, radius);
         let bbox = Aabb::two_point(&(*center - r_vec), &(*center + r_vec));
         Self {
@@ -30,12 +30,7 @@ impl Sphere {
             bbox,
         }
     }
-    pub(crate) fn moving(
-        center: &Point3,
-        radius: f64,
-        mat: Arc<dyn Material>,
-        center2: &Vec3,
-    ) -> Self {
+    pub fn moving(center: &Point3, radius: f64, mat: Arc<dyn Material>, center2: &Vec3) -> Self {
         let r_vec = Vec3::new(radius, radius, radius);
         let box1 = Aabb::two_point(&(*center - r_vec), &(*center + r_vec));
         let box2 = Aabb::two_point(&(*center2 - r_vec), &(*center2 + r_vec));
@@ -49,13 +44,15 @@ impl Sphere {
             bbox,
         }
     }
+    // moves from `center` at time 0 to `center2` at time 1 and holds still
+    // outside, where its bounding box would not reach
     pub(crate) fn sphere_center(&self, time: f64) -> Point3 {
-        self.center + self.center_vec * time
+        self.center + self.center_vec * time.clamp(0.0, 1.0)
     }
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Sphere {
+    fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
         let center: Vec3 = if self.is_moving {
             self.sphere_center(r.time())
         } else {
@@ -93,13 +90,19 @@ impl Hittable for Sphere {
         let rec: HitRecord = HitRecord::new(&p, t, &outward_normal, r, self.mat.clone(), u, v);
         Some(rec)
     }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, ray_t: Interval, _rng: &mut SampleRng) -> Option<HitRecord> {
+        self.intersect(r, ray_t)
+    }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
 
     fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
-        if let Some(_rec) = self.hit(
+        if let Some(_rec) = self.intersect(
             &Ray::new(origin, direction, 0.0),
             Interval::new(0.001, f64::INFINITY),
         ) {
@@ -112,17 +115,16 @@ impl Hittable for Sphere {
         }
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
         let dir = self.center - *origin;
         let dis_squared = dir.length_squared();
         let uvw = Onb::new(&dir);
-        uvw.local(&random_to_sphere(self.radius, dis_squared))
+        uvw.local(&random_to_sphere(self.radius, dis_squared, rng))
     }
 }
 
-fn random_to_sphere(radius: f64, dis_squared: f64) -> Vec3 {
-    let r1 = thread_rng().gen_range(0.0..1.0);
-    let r2 = thread_rng().gen_range(0.0..1.0);
+fn random_to_sphere(radius: f64, dis_squared: f64, rng: &mut SampleRng) -> Vec3 {
+    let (r1, r2) = rng.get_2d();
     let z = 1.0 + r2 * ((1.0 - radius * radius / dis_squared).sqrt() - 1.0);
 
     let phi = 2.0 * r1 * std::f64::consts::PI;
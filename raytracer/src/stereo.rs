@@ -0,0 +1,65 @@
+use crate::projection::Projection;
+
+// where the two views go in the image, the left eye first
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StereoLayout {
+    SideBySide,
+    OverUnder,
+}
+
+pub const STEREO_LAYOUTS: [&str; 2] = ["side_by_side", "over_under"];
+
+impl StereoLayout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "side_by_side" => Some(StereoLayout::SideBySide),
+            "over_under" => Some(StereoLayout::OverUnder),
+            _ => None,
+        }
+    }
+}
+
+// A view for each eye, `interocular` apart and turned in to meet at
+// `convergence` in front of the camera, both packed into one image
+#[derive(Copy, Clone, Debug)]
+pub struct Stereo {
+    pub interocular: f64,
+    pub convergence: f64,
+    pub layout: StereoLayout,
+}
+
+impl Stereo {
+    // the eyes of an orthographic view would see the very same image
+    pub fn check(projection: &Projection) -> Result<(), String> {
+        match projection {
+            Projection::Orthographic { .. } => Err(String::from(
+                "Stereo needs a perspective or panoramic projection",
+            )),
+            _ => Ok(()),
+        }
+    }
+    // the size of one eye's view in an image of `width` by `height` pixels
+    pub fn view_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.layout {
+            StereoLayout::SideBySide => ((width / 2).max(1), height),
+            StereoLayout::OverUnder => (width, (height / 2).max(1)),
+        }
+    }
+    // The eye that sees the point (x, y) of the image, -1 for the left and 1
+    // for the right, and where the point lies in its view. None for a row or
+    // column an odd size leaves over.
+    pub fn eye(&self, x: f64, y: f64, width: u32, height: u32) -> Option<(f64, f64, f64)> {
+        let (view_width, view_height) = self.view_size(width, height);
+        let (view_width, view_height) = (view_width as f64, view_height as f64);
+        let (eye, x, y) = match self.layout {
+            StereoLayout::SideBySide if x < view_width => (-1.0, x, y),
+            StereoLayout::SideBySide => (1.0, x - view_width, y),
+            StereoLayout::OverUnder if y < view_height => (-1.0, x, y),
+            StereoLayout::OverUnder => (1.0, x, y - view_height),
+        };
+        if x >= view_width || y >= view_height {
+            return None;
+        }
+        Some((eye, x, y))
+    }
+}
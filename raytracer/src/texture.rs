@@ -1,6 +1,7 @@
 use crate::color::Color;
 use crate::interval::Interval;
 use crate::perlin::Perlin;
+use crate::rng::SampleRng;
 use crate::rtw_stb_image::RTWImage;
 use crate::vec3::Point3;
 use std::sync::Arc;
@@ -37,7 +38,7 @@ pub struct CheckerTexture {
 }
 
 impl CheckerTexture {
-    pub fn _new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
         Self {
             inv_scale: 1.0 / scale,
             even,
@@ -117,9 +118,9 @@ pub struct NoiseTexture {
 }
 
 impl NoiseTexture {
-    pub fn new(_scale: f64) -> Self {
+    pub fn new(_scale: f64, rng: &mut SampleRng) -> Self {
         Self {
-            noise: Perlin::new(),
+            noise: Perlin::new(rng),
             scale: _scale,
         }
     }
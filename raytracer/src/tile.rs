@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+
+pub const TILE_SIZE: u32 = 16;
+
+// A block of pixels rendered as one unit of work
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub fn pixels(&self) -> u32 {
+        self.width * self.height
+    }
+}
+
+// The part of the image to render, in pixels or as fractions of the width
+// and height
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Crop {
+    Pixels {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Normalized {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+impl Crop {
+    // the pixels of an image of the given size it covers, fractions are
+    // rounded outwards and anything beyond the image is cut off
+    pub fn window(&self, image_width: u32, image_height: u32) -> Tile {
+        let (x0, y0, x1, y1) = match *self {
+            Crop::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => (
+                x.min(image_width),
+                y.min(image_height),
+                x.saturating_add(width).min(image_width),
+                y.saturating_add(height).min(image_height),
+            ),
+            Crop::Normalized {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                let scale = |v: f64, size: u32, round: fn(f64) -> f64| {
+                    round(v.clamp(0.0, 1.0) * size as f64) as u32
+                };
+                (
+                    scale(x, image_width, f64::floor),
+                    scale(y, image_height, f64::floor),
+                    scale(x + width, image_width, f64::ceil),
+                    scale(y + height, image_height, f64::ceil),
+                )
+            }
+        };
+        Tile {
+            x: x0,
+            y: y0,
+            width: x1.saturating_sub(x0),
+            height: y1.saturating_sub(y0),
+        }
+    }
+}
+
+// like on the command line: x,y,width,height
+impl fmt::Display for Crop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Crop::Pixels {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "{},{},{},{}", x, y, width, height),
+            Crop::Normalized {
+                x,
+                y,
+                width,
+                height,
+            } => write!(f, "{:?},{:?},{:?},{:?}", x, y, width, height),
+        }
+    }
+}
+
+// row by row from the top, the tiles on the right and bottom edges may be smaller
+pub fn split(width: u32, height: u32, size: u32) -> Vec<Tile> {
+    let mut tiles = vec![];
+    for y in (0..height).step_by(size as usize) {
+        for x in (0..width).step_by(size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: size.min(width - x),
+                height: size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+// The tiles of the whole image that fall into `window`, cut down to it, so a
+// crop is split like the full frame
+pub fn split_window(width: u32, height: u32, size: u32, window: Tile) -> Vec<Tile> {
+    split(width, height, size)
+        .into_iter()
+        .filter_map(|tile| {
+            let x = tile.x.max(window.x);
+            let y = tile.y.max(window.y);
+            let right = (tile.x + tile.width).min(window.x + window.width);
+            let bottom = (tile.y + tile.height).min(window.y + window.height);
+            if right > x && bottom > y {
+                Some(Tile {
+                    x,
+                    y,
+                    width: right - x,
+                    height: bottom - y,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn default_threads() -> u32 {
+    thread::available_parallelism().map_or(1, |n| n.get() as u32)
+}
+
+// Runs `work` on every tile with a work stealing scheduler. Each thread starts
+// with its own contiguous run of tiles and takes them from the front; once it
+// runs dry it steals from the back of the other queues, so slow tiles never
+// leave cores idle. The queues are only locked once per tile, and every thread
+// keeps its results to itself until the end. Results come back in no
+// particular order.
+pub fn render_tiles<T, F>(tiles: &[Tile], threads: u32, work: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(Tile) -> T + Sync,
+{
+    let threads = (threads.max(1) as usize).min(tiles.len().max(1));
+    let per_thread = (tiles.len() + threads - 1) / threads;
+    let queues: Vec<Mutex<VecDeque<Tile>>> = (0..threads)
+        .map(|n| {
+            let start = (n * per_thread).min(tiles.len());
+            let end = ((n + 1) * per_thread).min(tiles.len());
+            Mutex::new(tiles[start..end].iter().copied().collect())
+        })
+        .collect();
+
+    let queues = &queues;
+    let work = &work;
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|n| {
+                scope.spawn(move || {
+                    let mut results = vec![];
+                    while let Some(tile) = next_tile(queues, n) {
+                        results.push(work(tile));
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
+
+fn next_tile(queues: &[Mutex<VecDeque<Tile>>], own: usize) -> Option<Tile> {
+    if let Some(tile) = queues[own].lock().unwrap().pop_front() {
+        return Some(tile);
+    }
+    (1..queues.len()).find_map(|k| {
+        let victim = (own + k) % queues.len();
+        queues[victim].lock().unwrap().pop_back()
+    })
+}
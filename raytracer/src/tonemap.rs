@@ -0,0 +1,170 @@
+use crate::color::Color;
+use std::sync::Arc;
+
+// Compresses linear radiance into the displayable [0, 1] range
+pub trait ToneMapper: Send + Sync {
+    fn map(&self, color: Color) -> Color;
+}
+
+// clips everything above 1, the look of the original renders
+pub struct Clamp;
+
+impl ToneMapper for Clamp {
+    fn map(&self, color: Color) -> Color {
+        Color::new(
+            color.x.clamp(0.0, 1.0),
+            color.y.clamp(0.0, 1.0),
+            color.z.clamp(0.0, 1.0),
+        )
+    }
+}
+
+// Extended Reinhard on the luminance, so hues are kept. Radiance at `white`
+// maps to 1, an infinite white is the plain x / (1 + x) curve.
+pub struct Reinhard {
+    pub white: f64,
+}
+
+impl ToneMapper for Reinhard {
+    fn map(&self, color: Color) -> Color {
+        let l = color.luminance();
+        if l <= 0.0 {
+            return Color::black();
+        }
+        let mapped = l * (1.0 + l / (self.white * self.white)) / (1.0 + l);
+        color * (mapped / l)
+    }
+}
+
+// Krzysztof Narkowicz's fit of the ACES filmic curve
+pub struct Aces;
+
+impl Aces {
+    fn curve(x: f64) -> f64 {
+        let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+        ((x * (a * x + b)) / (x * (c * x + d) + e)).clamp(0.0, 1.0)
+    }
+}
+
+impl ToneMapper for Aces {
+    fn map(&self, color: Color) -> Color {
+        // the fit is made for inputs pre-exposed by 0.6
+        let color = color * 0.6;
+        Color::new(
+            Aces::curve(color.x),
+            Aces::curve(color.y),
+            Aces::curve(color.z),
+        )
+    }
+}
+
+// John Hable's Uncharted 2 filmic curve, normalized so that `white` maps to 1
+pub struct Hable {
+    pub white: f64,
+}
+
+impl Hable {
+    fn curve(x: f64) -> f64 {
+        let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+        ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+    }
+}
+
+impl ToneMapper for Hable {
+    fn map(&self, color: Color) -> Color {
+        let exposure_bias = 2.0;
+        let scale = 1.0 / Hable::curve(self.white);
+        let channel = |x: f64| (Hable::curve(x.max(0.0) * exposure_bias) * scale).clamp(0.0, 1.0);
+        Color::new(channel(color.x), channel(color.y), channel(color.z))
+    }
+}
+
+pub const OPERATORS: [&str; 4] = ["clamp", "reinhard", "aces", "hable"];
+
+// `white` is the radiance that becomes pure white, for the curves that have one
+pub fn operator(name: &str, white: Option<f64>) -> Option<Arc<dyn ToneMapper>> {
+    match name {
+        "clamp" => Some(Arc::new(Clamp)),
+        "reinhard" => Some(Arc::new(Reinhard {
+            white: white.unwrap_or(f64::INFINITY),
+        })),
+        "aces" => Some(Arc::new(Aces)),
+        "hable" => Some(Arc::new(Hable {
+            white: white.unwrap_or(11.2),
+        })),
+        _ => None,
+    }
+}
+
+// encoding of the tone mapped values
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Transfer {
+    // the plain square root the renderer always used
+    Gamma2,
+    Srgb,
+    Linear,
+}
+
+impl Transfer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "gamma2" => Some(Transfer::Gamma2),
+            "srgb" => Some(Transfer::Srgb),
+            "linear" => Some(Transfer::Linear),
+            _ => None,
+        }
+    }
+    pub fn encode(&self, linear: f64) -> f64 {
+        if linear <= 0.0 {
+            return 0.0;
+        }
+        match self {
+            Transfer::Gamma2 => linear.sqrt(),
+            Transfer::Srgb => {
+                if linear <= 0.0031308 {
+                    12.92 * linear
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                }
+            }
+            Transfer::Linear => linear,
+        }
+    }
+}
+
+// How a render becomes a displayable image: exposure in stops, then the
+// tone curve, then the transfer function
+#[derive(Clone)]
+pub struct ToneMapping {
+    pub operator: Arc<dyn ToneMapper>,
+    pub exposure: f64,
+    pub transfer: Transfer,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            operator: Arc::new(Clamp),
+            exposure: 0.0,
+            transfer: Transfer::Gamma2,
+        }
+    }
+}
+
+impl ToneMapping {
+    // display channels in [0, 1], NaN samples become black
+    pub fn apply(&self, color: Color) -> [f64; 3] {
+        let mut color = color * 2f64.powf(self.exposure);
+        for c in [&mut color.x, &mut color.y, &mut color.z] {
+            if c.is_nan() {
+                *c = 0.0;
+            }
+        }
+        let mapped = self.operator.map(color);
+        [
+            self.transfer.encode(mapped.x).min(1.0),
+            self.transfer.encode(mapped.y).min(1.0),
+            self.transfer.encode(mapped.z).min(1.0),
+        ]
+    }
+}
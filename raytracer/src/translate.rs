@@ -2,6 +2,7 @@ use crate::aabb::{add, Aabb};
 use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::vec3::{Point3, Vec3};
 use std::sync::Arc;
 
@@ -23,10 +24,10 @@ impl Translate {
 }
 
 impl Hittable for Translate {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
         let offset_r = Ray::new(&(r.origin() - self.offset), &r.direction(), r.time());
 
-        if let Some(mut rec) = self.object.hit(&offset_r, ray_t) {
+        if let Some(mut rec) = self.object.hit(&offset_r, ray_t, rng) {
             rec.p += self.offset;
             return Some(rec);
         }
@@ -41,8 +42,8 @@ impl Hittable for Translate {
         self.object.pdf_value(&(*origin - self.offset), direction)
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
-        self.object.random(&(*origin - self.offset))
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        self.object.random(&(*origin - self.offset), rng)
     }
 }
 
@@ -95,7 +96,7 @@ impl RotateY {
 }
 
 impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, ray_t: Interval, rng: &mut SampleRng) -> Option<HitRecord> {
         let mut ori = r.origin();
         let mut dir = r.direction();
 
@@ -105,7 +106,7 @@ impl Hittable for RotateY {
         dir[2] = r.direction()[0] * self.sin_theta + r.direction()[2] * self.cos_theta;
         let rotated_r = Ray::new(&ori, &dir, r.time());
 
-        if let Some(mut rec) = self.object.hit(&rotated_r, ray_t) {
+        if let Some(mut rec) = self.object.hit(&rotated_r, ray_t, rng) {
             let mut p = rec.p;
             p[0] = rec.p[0] * self.cos_theta + rec.p[2] * self.sin_theta;
             p[2] = rec.p[0] * -self.sin_theta + rec.p[2] * self.cos_theta;
@@ -137,7 +138,7 @@ impl Hittable for RotateY {
         self.object.pdf_value(&ori, &dir)
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
-        self.object.random(origin)
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        self.object.random(origin, rng)
     }
 }
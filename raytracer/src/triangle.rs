@@ -3,8 +3,8 @@ use crate::hittable::{HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::rng::SampleRng;
 use crate::vec3::{cross, dot, unit_vector, Point3, Vec3};
-use rand::{thread_rng, Rng};
 use std::sync::Arc;
 
 pub struct Triangle {
@@ -45,8 +45,8 @@ impl Triangle {
     }
 }
 
-impl Hittable for Triangle {
-    fn hit(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
+impl Triangle {
+    fn intersect(&self, r: &Ray, ray_t: Interval) -> Option<HitRecord> {
         let denom = dot(&self.normal, &r.direction());
 
         if denom.abs() < 1e-8 {
@@ -77,13 +77,19 @@ impl Hittable for Triangle {
         );
         Some(rec)
     }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, ray_t: Interval, _rng: &mut SampleRng) -> Option<HitRecord> {
+        self.intersect(r, ray_t)
+    }
 
     fn bounding_box(&self) -> Aabb {
         self.bbox.clone()
     }
 
     fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
-        if let Some(rec) = self.hit(
+        if let Some(rec) = self.intersect(
             &Ray::new(origin, direction, 0.0),
             Interval::new(0.001, f64::INFINITY),
         ) {
@@ -96,9 +102,9 @@ impl Hittable for Triangle {
         }
     }
 
-    fn random(&self, origin: &Point3) -> Vec3 {
-        let a = thread_rng().gen_range(0.0..1.0);
-        let b = thread_rng().gen_range(0.0..1.0 - a);
+    fn random(&self, origin: &Point3, rng: &mut SampleRng) -> Vec3 {
+        let (a, b) = rng.get_2d();
+        let b = b * (1.0 - a);
         let p = self.q + self.u * a + self.v * b;
         p - *origin
     }
@@ -1,4 +1,5 @@
-use rand::{thread_rng, Rng};
+use crate::rng::SampleRng;
+use rand::Rng;
 use std::f64;
 use std::ops::{
     Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
@@ -6,9 +7,9 @@ use std::ops::{
 
 #[derive(Debug, Clone, Copy)]
 pub struct Vec3 {
-    pub(crate) x: f64,
-    pub(crate) y: f64,
-    pub(crate) z: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 }
 
 impl Vec3 {
@@ -33,16 +34,14 @@ impl Vec3 {
     pub fn length(&self) -> f64 {
         self.length_squared().sqrt()
     }
-    pub fn random() -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn random(rng: &mut SampleRng) -> Vec3 {
         Vec3::new(
             rng.gen_range(0.0..1.0),
             rng.gen_range(0.0..1.0),
             rng.gen_range(0.0..1.0),
         )
     }
-    pub fn random_in(min: f64, max: f64) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn random_in(min: f64, max: f64, rng: &mut SampleRng) -> Vec3 {
         Vec3::new(
             rng.gen_range(min..max),
             rng.gen_range(min..max),
@@ -68,28 +67,31 @@ pub fn cross(u: &Vec3, v: &Vec3) -> Vec3 {
 pub fn unit_vector(v: &Vec3) -> Vec3 {
     *v / v.length()
 }
-pub fn random_in_unit_sphere() -> Vec3 {
+pub fn random_in_unit_sphere(rng: &mut SampleRng) -> Vec3 {
     loop {
-        let p = Vec3::random_in(-1.0, 1.0);
+        let p = Vec3::random_in(-1.0, 1.0, rng);
         if p.length_squared() < 1.0 {
             return p;
         }
     }
 }
-pub fn random_unit_vector() -> Vec3 {
-    unit_vector(&random_in_unit_sphere())
+pub fn random_unit_vector(rng: &mut SampleRng) -> Vec3 {
+    let (r1, r2) = rng.get_2d();
+    let z = 1.0 - 2.0 * r1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * r2 * std::f64::consts::PI;
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
 }
-pub fn _random_on_hemisphere(normal: &Vec3) -> Vec3 {
-    let on_unit_sphere = random_unit_vector();
+pub fn _random_on_hemisphere(normal: &Vec3, rng: &mut SampleRng) -> Vec3 {
+    let on_unit_sphere = random_unit_vector(rng);
     if dot(&on_unit_sphere, normal) > 0.0 {
         on_unit_sphere
     } else {
         -on_unit_sphere
     }
 }
-pub fn random_cosine_direction() -> Vec3 {
-    let r1: f64 = thread_rng().gen_range(0.0..1.0);
-    let r2: f64 = thread_rng().gen_range(0.0..1.0);
+pub fn random_cosine_direction(rng: &mut SampleRng) -> Vec3 {
+    let (r1, r2) = rng.get_2d();
 
     let phi = 2.0 * r1 * std::f64::consts::PI;
     Vec3 {
@@ -98,14 +100,20 @@ pub fn random_cosine_direction() -> Vec3 {
         z: (1.0 - r2).sqrt(),
     }
 }
-pub fn random_in_unit_disk() -> Vec3 {
-    loop {
-        let mut rng = rand::thread_rng();
-        let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-        if p.length_squared() < 1.0 {
-            return p;
-        }
+// Shirley and Chiu's concentric mapping, which keeps the strata of the square intact
+pub fn random_in_unit_disk(rng: &mut SampleRng) -> Vec3 {
+    let (u, v) = rng.get_2d();
+    let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return Vec3::new(0.0, 0.0, 0.0);
     }
+    let quarter_pi = std::f64::consts::FRAC_PI_4;
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, quarter_pi * (b / a))
+    } else {
+        (b, 2.0 * quarter_pi - quarter_pi * (a / b))
+    };
+    Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
 }
 pub fn reflect(v: &Vec3, n: &Vec3) -> Vec3 {
     *v - *n * 2.0 * dot(v, n)